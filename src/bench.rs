@@ -0,0 +1,178 @@
+//! `chess_like bench [iterations]` times the position representations against each other
+
+use std::time::Instant;
+
+use rand::{Rng, thread_rng};
+
+use game::Game;
+use game::pieces::*;
+use game::action::*;
+use game::bitboard::*;
+
+const BOARD: u32 = 10;
+const PIECES_PER_TEAM: u32 = 15;
+
+/// The representation `Game` used before the board was indexed by square
+struct VecPosition {
+    pieces: Vec<Piece>,
+}
+
+impl VecPosition {
+    fn get(&self, x: i32, y: i32) -> Option<&Piece> {
+        self.pieces.iter().find(|p| p.x() == x && p.y() == y)
+    }
+
+    fn remove(&mut self, piece: &Piece) {
+        if let Some(i) = self.pieces.iter().position(|p| p == piece) {
+            self.pieces.remove(i);
+        }
+    }
+
+    fn make(&mut self, action: &Action) {
+        match *action {
+            Action::Place(ref p) => self.pieces.push(p.clone()),
+            Action::Remove(ref p) => self.remove(p),
+            Action::Move(ref p, dx, dy) => {
+                let mut new = p.clone();
+                new.apply(Move::from(MoveDir::new(dx, dy)));
+                self.remove(p);
+                self.pieces.push(new);
+            },
+            Action::Upgrade(ref sacrifice, ref old, ref new) => {
+                self.remove(sacrifice);
+                self.remove(old);
+                self.pieces.push(new.clone());
+            },
+            Action::Composite(Composite::Capture(ref c)) => {
+                self.make(&c.remove_action);
+                self.make(&c.move_action);
+            },
+            Action::Pass(_) | Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => (),
+        }
+    }
+
+    fn unmake(&mut self, action: &Action) {
+        match *action {
+            Action::Place(ref p) => self.remove(p),
+            Action::Remove(ref p) => self.pieces.push(p.clone()),
+            Action::Move(ref p, dx, dy) => {
+                let mut new = p.clone();
+                new.apply(Move::from(MoveDir::new(dx, dy)));
+                self.remove(&new);
+                self.pieces.push(p.clone());
+            },
+            Action::Upgrade(ref sacrifice, ref old, ref new) => {
+                self.remove(new);
+                self.pieces.push(sacrifice.clone());
+                self.pieces.push(old.clone());
+            },
+            Action::Composite(Composite::Capture(ref c)) => {
+                self.unmake(&c.move_action);
+                self.unmake(&c.remove_action);
+            },
+            Action::Pass(_) | Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => (),
+        }
+    }
+}
+
+// a crowded board, and every action the team to move could take on it
+fn setup() -> (Game, Vec<Action>) {
+    let teams = vec![Alliance::new("Team 1", PIECES_PER_TEAM, [1.0; 4]),
+                     Alliance::new("Team 2", PIECES_PER_TEAM, [1.0; 4])];
+    let mut game = Game::from_vec(BOARD, BOARD, Vec::new(), teams);
+    let mut rng = thread_rng();
+
+    while game.teams()[game.turn()].pieces_left > 0 {
+        let (x, y) = (rng.gen_range(0, BOARD), rng.gen_range(0, BOARD));
+        if let Ok(action) = game.place(x, y, game.turn()) {
+            let _ = game.do_action(action);
+        }
+    }
+
+    let mut actions = Vec::new();
+    for p in game.board().iter().filter(|p| p.team() == game.turn()) {
+        for m in p.poss_moves.moves() {
+            let (x, y) = (p.x() + m.dx(), p.y() + m.dy());
+            if x >= 0 && y >= 0 {
+                if let Ok(action) = game.check_move(p, x as u32, y as u32, true) {
+                    actions.push(action);
+                }
+            }
+        }
+    }
+
+    (game, actions)
+}
+
+fn time<F: FnMut()>(name: &str, ops: u64, mut f: F) {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    let nanos = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+
+    println!("{:<40} {:>10.1} ns/op", name, nanos as f64 / ops as f64);
+}
+
+pub fn run(iterations: u64) {
+    let (mut game, actions) = setup();
+    let ops = iterations * actions.len() as u64;
+    println!("{} pieces, {} actions, {} iterations", game.board().iter().count(), actions.len(), iterations);
+
+    let mut vec = VecPosition { pieces: game.board().iter().cloned().collect() };
+    let mut table = MoveSetTable::new();
+    let mut pos = Position::from_game(&game, &mut table).unwrap();
+    let bb_actions: Vec<BbAction> = actions.iter().map(|a| pos.translate(a, &mut table).unwrap()).collect();
+    let squares = (BOARD * BOARD) as u64;
+
+    time("Vec<Piece> make/unmake", ops, || for _ in 0..iterations {
+        for action in &actions {
+            vec.make(action);
+            vec.unmake(action);
+        }
+    });
+    time("Game make/unmake", ops, || for _ in 0..iterations {
+        for action in &actions {
+            let _ = game.do_action(action.clone());
+            game.undo_last();
+        }
+    });
+    time("Position make/unmake", ops, || for _ in 0..iterations {
+        for &action in &bb_actions {
+            pos.make(action);
+            pos.unmake(action);
+        }
+    });
+
+    let mut found = 0;
+    time("Vec<Piece> lookup every square", iterations * squares, || for _ in 0..iterations {
+        for sq in 0..squares as i32 {
+            found += vec.get(sq % BOARD as i32, sq / BOARD as i32).is_some() as u32;
+        }
+    });
+    time("Game lookup every square", iterations * squares, || for _ in 0..iterations {
+        for sq in 0..squares as u32 {
+            found += game.get_piece(sq % BOARD, sq / BOARD).is_some() as u32;
+        }
+    });
+    time("Position lookup every square", iterations * squares, || for _ in 0..iterations {
+        for sq in 0..squares as u8 {
+            found += pos.piece_at(sq).is_some() as u32;
+        }
+    });
+
+    time("Vec<Piece> copy", iterations, || for _ in 0..iterations {
+        found += vec.pieces.clone().len() as u32;
+    });
+    time("Position copy", iterations, || for _ in 0..iterations {
+        let copy = pos;
+        found += copy.turn() as u32;
+    });
+
+    // every make was unmade, so converting back has to give the same board
+    let board = game.board().clone();
+    pos.write_to(&mut game, &table);
+    assert!(*game.board() == board, "the bitboard did not round trip");
+
+    // keep the lookups from being optimized away
+    println!("({})", found);
+}
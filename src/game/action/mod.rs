@@ -12,18 +12,96 @@ pub enum Action {
     Place(Piece),
     Remove(Piece),
     Move(Piece, i32, i32),
-    Upgrade(Piece, Piece),
+    // the sacrificed piece, the piece it merges into and the piece that results
+    Upgrade(Piece, Piece, Piece),
     Composite(Composite),
+    // the team gave up its turn
+    Pass(usize),
+    // the first team offers an alliance to the second, which holds once both have offered
+    Ally(usize, usize),
+    // the first team withdraws its offer to the second, ending their alliance
+    Break(usize, usize),
+    // the team is out of the game, from running out of time or a bot that failed
+    Forfeit(usize),
 }
 
 impl Debug for Action {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        match self {
-            &Action::Place(_) => write!(f, "Place"),
-            &Action::Remove(_) => write!(f, "Remove"),
-            &Action::Move(_,_,_) => write!(f, "Move"),
-            &Action::Upgrade(_,_) => write!(f, "Upgrade"),
-            &Action::Composite(ref c) => write!(f, "Composite {:?}", c),
+        match *self {
+            Action::Place(_) => write!(f, "Place"),
+            Action::Remove(_) => write!(f, "Remove"),
+            Action::Move(_,_,_) => write!(f, "Move"),
+            Action::Upgrade(_,_,_) => write!(f, "Upgrade"),
+            Action::Composite(ref c) => write!(f, "Composite {:?}", c),
+            Action::Pass(team) => write!(f, "Pass {}", team),
+            Action::Ally(team, other) => write!(f, "Ally {} {}", team, other),
+            Action::Break(team, other) => write!(f, "Break {} {}", team, other),
+            Action::Forfeit(team) => write!(f, "Forfeit {}", team),
+        }
+    }
+}
+
+impl Action {
+    /// the team that takes this action
+    pub fn team(&self) -> usize {
+        match *self {
+            Action::Place(ref p) |
+            Action::Remove(ref p) |
+            Action::Move(ref p, _, _) |
+            Action::Upgrade(ref p, _, _) => p.team(),
+            Action::Composite(Composite::Capture(ref c)) => c.move_action.team(),
+            Action::Pass(team) |
+            Action::Ally(team, _) |
+            Action::Break(team, _) |
+            Action::Forfeit(team) => team,
+        }
+    }
+
+    /// whether the next team moves after this action, which diplomacy does not use up
+    pub fn ends_turn(&self) -> bool {
+        !matches!(*self, Action::Ally(_, _) | Action::Break(_, _))
+    }
+
+    /// every piece this action names, as it was before and after the action
+    pub fn pieces(&self) -> Vec<&Piece> {
+        match *self {
+            Action::Place(ref p) |
+            Action::Remove(ref p) |
+            Action::Move(ref p, _, _) => vec![p],
+            Action::Upgrade(ref sacrifice, ref old, ref new) => vec![sacrifice, old, new],
+            Action::Composite(Composite::Capture(ref c)) => {
+                let mut pieces = c.remove_action.pieces();
+                pieces.extend(c.move_action.pieces());
+                pieces
+            },
+            Action::Pass(_) | Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => Vec::new(),
+        }
+    }
+
+    /// a copy of this action with every piece it names replaced by `f` of it
+    pub fn map_pieces(&self, f: &mut dyn FnMut(&Piece) -> Piece) -> Action {
+        match *self {
+            Action::Place(ref p) => Action::Place(f(p)),
+            Action::Remove(ref p) => Action::Remove(f(p)),
+            Action::Move(ref p, dx, dy) => Action::Move(f(p), dx, dy),
+            Action::Upgrade(ref sacrifice, ref old, ref new) => Action::Upgrade(f(sacrifice), f(old), f(new)),
+            Action::Composite(Composite::Capture(ref c)) => Action::Composite(Composite::Capture(Arc::new(CaptureVal::from([
+                c.remove_action.map_pieces(f),
+                c.move_action.map_pieces(f),
+            ])))),
+            ref other => other.clone(),
+        }
+    }
+
+    /// the squares this action touches, where pieces leave from and arrive on
+    pub fn squares(&self) -> Vec<(i32, i32)> {
+        match *self {
+            Action::Place(ref p) |
+            Action::Remove(ref p) => vec![(p.x(), p.y())],
+            Action::Move(ref p, dx, dy) => vec![(p.x(), p.y()), (p.x() + dx, p.y() + dy)],
+            Action::Upgrade(ref sacrifice, ref old, _) => vec![(sacrifice.x(), sacrifice.y()), (old.x(), old.y())],
+            Action::Composite(Composite::Capture(ref c)) => c.move_action.squares(),
+            Action::Pass(_) | Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => Vec::new(),
         }
     }
 }
@@ -35,8 +113,8 @@ pub enum Composite {
 
 impl Debug for Composite {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        match self {
-            &Composite::Capture(_) => write!(f, "Capture"),
+        match *self {
+            Composite::Capture(_) => write!(f, "Capture"),
         }
     }
 }
@@ -0,0 +1,182 @@
+use std::f64::consts::PI;
+
+use game::pieces::Piece;
+use game::action::*;
+
+// seconds each step of an animation takes
+const STEP: f64 = 0.2;
+// how much bigger a piece gets at the peak of a pulse
+const PULSE: f64 = 0.35;
+
+/// A piece drawn somewhere between two squares while an action plays out
+#[derive(Debug, Clone)]
+struct Tween {
+    piece: Piece,
+    from: (f64, f64), to: (f64, f64),
+    fade: bool, pulse: bool,
+    delay: f64, duration: f64,
+}
+
+impl Tween {
+    fn new(piece: &Piece, from: (i32, i32), to: (i32, i32)) -> Self {
+        Self {
+            piece: piece.clone(),
+            from: (from.0 as f64, from.1 as f64), to: (to.0 as f64, to.1 as f64),
+            fade: false, pulse: false,
+            delay: 0.0, duration: STEP,
+        }
+    }
+
+    fn still(piece: &Piece) -> Self {
+        Self::new(piece, (piece.x(), piece.y()), (piece.x(), piece.y()))
+    }
+
+    fn fade(mut self) -> Self {
+        self.fade = true;
+        self
+    }
+
+    fn pulse(mut self) -> Self {
+        self.pulse = true;
+        self
+    }
+
+    fn after(mut self, delay: f64) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// Where and how to draw a piece that is being animated
+pub struct Frame<'a> {
+    pub piece: &'a Piece,
+    pub x: f64, pub y: f64,
+    pub scale: f64, pub alpha: f32,
+}
+
+/// Plays short animations for the actions applied to the board
+pub struct Animator {
+    pub enabled: bool,
+    tweens: Vec<Tween>,
+    // the ids of the pieces being drawn by a tween instead
+    hidden: Vec<u32>,
+    elapsed: f64,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Self { enabled: true, tweens: Vec::new(), hidden: Vec::new(), elapsed: 0.0 }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.tweens.is_empty()
+    }
+
+    pub fn is_hidden(&self, id: u32) -> bool {
+        self.hidden.contains(&id)
+    }
+
+    pub fn clear(&mut self) {
+        self.tweens.clear();
+        self.hidden.clear();
+        self.elapsed = 0.0;
+    }
+
+    /// starts the animation for `action`, which has just been applied
+    pub fn start(&mut self, action: &Action) {
+        self.clear();
+
+        if !self.enabled {
+            return;
+        }
+
+        match *action {
+            Action::Move(ref p, dx, dy) => {
+                self.tweens.push(Tween::new(p, (p.x(), p.y()), (p.x() + dx, p.y() + dy)));
+                self.hidden.push(p.id());
+            },
+            Action::Upgrade(ref sacrifice, ref old, ref new) => {
+                self.tweens.push(Tween::still(old));
+                self.tweens.push(Tween::new(sacrifice, (sacrifice.x(), sacrifice.y()), (old.x(), old.y())));
+                self.tweens.push(Tween::still(new).pulse().after(STEP));
+                self.hidden.push(new.id());
+            },
+            Action::Composite(Composite::Capture(ref c)) => {
+                if let Action::Remove(ref victim) = c.remove_action {
+                    self.tweens.push(Tween::still(victim).fade());
+                }
+                if let Action::Move(ref p, dx, dy) = c.move_action {
+                    self.tweens.push(Tween::new(p, (p.x(), p.y()), (p.x() + dx, p.y() + dy)));
+                    self.hidden.push(p.id());
+                }
+            },
+            Action::Place(_) | Action::Remove(_) | Action::Pass(_) |
+            Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => (),
+        }
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        if !self.is_busy() {
+            return;
+        }
+
+        self.elapsed += dt;
+
+        let elapsed = self.elapsed;
+        if self.tweens.iter().all(|t| elapsed >= t.delay + t.duration) {
+            self.clear();
+        }
+    }
+
+    /// every piece that should be drawn this frame by the animator
+    pub fn frames(&self) -> Vec<Frame<'_>> {
+        self.tweens.iter()
+            .filter(|t| self.elapsed >= t.delay && self.elapsed < t.delay + t.duration)
+            .map(|t| {
+                let progress = (self.elapsed - t.delay) / t.duration;
+                let eased = progress * progress * (3.0 - 2.0 * progress);
+
+                Frame {
+                    piece: &t.piece,
+                    x: t.from.0 + (t.to.0 - t.from.0) * eased,
+                    y: t.from.1 + (t.to.1 - t.from.1) * eased,
+                    scale: if t.pulse { 1.0 + PULSE * (PI * progress).sin() } else { 1.0 },
+                    alpha: if t.fade { (1.0 - progress) as f32 } else { 1.0 },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn move_finishes() {
+        let p = Piece::new(1, 1, 0).with_id(7);
+        let mut animator = Animator::new();
+
+        animator.start(&Action::Move(p, 1, 0));
+        assert!(animator.is_busy());
+        assert!(animator.is_hidden(7));
+
+        animator.update(STEP / 2.0);
+        let frames = animator.frames();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].x, 1.5);
+
+        animator.update(STEP);
+        assert!(!animator.is_busy());
+        assert!(!animator.is_hidden(7));
+    }
+
+    #[test]
+    fn disabled() {
+        let mut animator = Animator::new();
+        animator.enabled = false;
+
+        animator.start(&Action::Move(Piece::new(1, 1, 0), 1, 0));
+        assert!(!animator.is_busy());
+    }
+}
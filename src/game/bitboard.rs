@@ -0,0 +1,379 @@
+//! A compact, `Copy` position for search
+//!
+//! Every square is a bit in a `u128`, so boards of up to 128 squares are supported. The
+//! pieces of each team are split by level into separate bitboards, and the move set of the
+//! piece on each square is stored as an id into a `MoveSetTable`, which is shared between
+//! all positions derived from the same game. The id of each piece travels with it, so a
+//! position written back onto its game keeps every piece's identity.
+
+use std::collections::HashMap;
+
+use game::Game;
+use game::pieces::*;
+use game::action::*;
+use game::board::Board;
+
+pub const MAX_SQUARES: usize = 128;
+pub const MAX_TEAMS: usize = 4;
+pub const MAX_LEVELS: usize = 8;
+
+/// Interns every move set used by a game, so that positions can refer to them by id
+#[derive(Debug, Clone, Default)]
+pub struct MoveSetTable {
+    sets: Vec<MoveDirSet>,
+    ids: HashMap<MoveDirSet, u16>,
+}
+
+impl MoveSetTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, set: &MoveDirSet) -> u16 {
+        if let Some(&id) = self.ids.get(set) {
+            return id;
+        }
+
+        let id = self.sets.len() as u16;
+        self.sets.push(set.clone());
+        self.ids.insert(set.clone(), id);
+        id
+    }
+
+    pub fn get(&self, id: u16) -> &MoveDirSet {
+        &self.sets[id as usize]
+    }
+}
+
+/// A piece as the bitboard sees it, on square `sq = y * width + x`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BbPiece {
+    pub sq: u8, pub team: u8, pub level: u8, pub moves: u16, pub id: u32,
+}
+
+/// An `Action` translated for a `Position`, holding everything needed to unmake it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BbAction {
+    Place(BbPiece),
+    Remove(BbPiece),
+    Move(BbPiece, u8),
+    Upgrade { sacrifice: BbPiece, old: BbPiece, new: BbPiece },
+    Capture { victim: BbPiece, mover: BbPiece },
+    Pass(u8),
+}
+
+impl BbAction {
+    pub fn team(&self) -> u8 {
+        match *self {
+            BbAction::Place(p) |
+            BbAction::Remove(p) |
+            BbAction::Move(p, _) |
+            BbAction::Upgrade { sacrifice: p, .. } |
+            BbAction::Capture { mover: p, .. } => p.team,
+            BbAction::Pass(team) => team,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    width: u8, height: u8,
+    teams: u8, turn: u8,
+    // one bit per team
+    eliminated: u8,
+    pieces_left: [u32; MAX_TEAMS],
+    // occupancy[team][level - 1]
+    occupancy: [[u128; MAX_LEVELS]; MAX_TEAMS],
+    moves: [u16; MAX_SQUARES],
+    ids: [u32; MAX_SQUARES],
+}
+
+impl Position {
+    pub fn from_game(game: &Game, table: &mut MoveSetTable) -> Result<Self, String> {
+        let (width, height) = (game.board.width(), game.board.height());
+
+        if (width * height) as usize > MAX_SQUARES {
+            return Err(format!("a {}x{} board has more than {} squares", width, height, MAX_SQUARES));
+        }
+        if game.teams.len() > MAX_TEAMS {
+            return Err(format!("more than {} teams", MAX_TEAMS));
+        }
+        if game.board.walls().next().is_some() {
+            return Err(String::from("positions do not track walls"));
+        }
+
+        let mut pos = Position {
+            width: width as u8, height: height as u8,
+            teams: game.teams.len() as u8, turn: game.turn as u8,
+            eliminated: 0,
+            pieces_left: [0; MAX_TEAMS],
+            occupancy: [[0; MAX_LEVELS]; MAX_TEAMS],
+            moves: [0; MAX_SQUARES],
+            ids: [0; MAX_SQUARES],
+        };
+
+        for (i, team) in game.teams.iter().enumerate() {
+            pos.pieces_left[i] = team.pieces_left;
+            if team.eliminated {
+                pos.eliminated |= 1 << i;
+            }
+        }
+
+        for p in game.board.iter() {
+            let p = pos.piece(p, table)?;
+            pos.put(p);
+        }
+
+        Ok(pos)
+    }
+
+    /// copies this position back onto `game`, which must be the game it was made from
+    pub fn write_to(&self, game: &mut Game, table: &MoveSetTable) {
+        let mut board = Board::new(self.width as u32, self.height as u32);
+
+        for p in self.pieces() {
+            let (x, y) = self.coords(p.sq);
+            let piece = Piece::from_parts(x, y, p.team as usize, p.level as u32, table.get(p.moves).clone()).with_id(p.id);
+            // every square holds at most one piece, so this can't fail
+            let _ = board.insert(piece);
+        }
+
+        for (i, team) in game.teams.iter_mut().enumerate() {
+            team.pieces_left = self.pieces_left[i];
+            team.eliminated = self.eliminated & (1 << i) != 0;
+        }
+
+        game.board = board;
+        game.turn = self.turn as usize;
+    }
+
+    fn coords(&self, sq: u8) -> (i32, i32) {
+        let sq = sq as i32;
+        (sq % self.width as i32, sq / self.width as i32)
+    }
+
+    pub fn square(&self, x: i32, y: i32) -> Option<u8> {
+        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
+            Some((y * self.width as i32 + x) as u8)
+        } else {
+            None
+        }
+    }
+
+    fn piece(&self, p: &Piece, table: &mut MoveSetTable) -> Result<BbPiece, String> {
+        let sq = self.square(p.x(), p.y()).ok_or_else(|| format!("{} is not on the board", p))?;
+
+        if p.level() == 0 || p.level() as usize > MAX_LEVELS {
+            return Err(format!("level {} does not fit in a bitboard", p.level()));
+        }
+        if p.team() >= self.teams as usize {
+            return Err(format!("{} belongs to a team that does not exist", p));
+        }
+
+        Ok(BbPiece { sq, team: p.team() as u8, level: p.level() as u8, moves: table.intern(&p.poss_moves), id: p.id() })
+    }
+
+    pub fn turn(&self) -> u8 {
+        self.turn
+    }
+
+    pub fn occupied(&self) -> u128 {
+        (0..self.teams as usize).fold(0, |acc, team| acc | self.team_occupancy(team as u8))
+    }
+
+    pub fn team_occupancy(&self, team: u8) -> u128 {
+        self.occupancy[team as usize].iter().fold(0, |acc, bits| acc | bits)
+    }
+
+    pub fn level_occupancy(&self, team: u8, level: u8) -> u128 {
+        self.occupancy[team as usize][level as usize - 1]
+    }
+
+    pub fn piece_at(&self, sq: u8) -> Option<BbPiece> {
+        let bit = 1u128 << sq;
+
+        if self.occupied() & bit == 0 {
+            return None;
+        }
+
+        for team in 0..self.teams {
+            for level in 1..=MAX_LEVELS as u8 {
+                if self.level_occupancy(team, level) & bit != 0 {
+                    return Some(BbPiece { sq, team, level, moves: self.moves[sq as usize], id: self.ids[sq as usize] });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn pieces(&self) -> Vec<BbPiece> {
+        let mut pieces = Vec::new();
+        let mut occupied = self.occupied();
+
+        while occupied != 0 {
+            let sq = occupied.trailing_zeros() as u8;
+            occupied &= occupied - 1;
+
+            if let Some(p) = self.piece_at(sq) {
+                pieces.push(p);
+            }
+        }
+
+        pieces
+    }
+
+    fn put(&mut self, p: BbPiece) {
+        self.occupancy[p.team as usize][p.level as usize - 1] |= 1 << p.sq;
+        self.moves[p.sq as usize] = p.moves;
+        self.ids[p.sq as usize] = p.id;
+    }
+
+    fn take(&mut self, p: BbPiece) {
+        self.occupancy[p.team as usize][p.level as usize - 1] &= !(1 << p.sq);
+        // keep empty squares at 0, so equal positions compare equal
+        self.moves[p.sq as usize] = 0;
+        self.ids[p.sq as usize] = 0;
+    }
+
+    fn next_turn(&mut self) {
+        for _ in 0..self.teams {
+            self.turn = (self.turn + 1) % self.teams;
+            if self.eliminated & (1 << self.turn) == 0 {
+                break;
+            }
+        }
+    }
+
+    /// translates an action on the game this position came from
+    pub fn translate(&self, action: &Action, table: &mut MoveSetTable) -> Result<BbAction, String> {
+        Ok(match *action {
+            Action::Place(ref p) => BbAction::Place(self.piece(p, table)?),
+            Action::Remove(ref p) => BbAction::Remove(self.piece(p, table)?),
+            Action::Move(ref p, dx, dy) => {
+                let to = self.square(p.x() + dx, p.y() + dy).ok_or("the move leaves the board")?;
+                BbAction::Move(self.piece(p, table)?, to)
+            },
+            Action::Upgrade(ref sacrifice, ref old, ref new) => BbAction::Upgrade {
+                sacrifice: self.piece(sacrifice, table)?,
+                old: self.piece(old, table)?,
+                new: self.piece(new, table)?,
+            },
+            Action::Composite(Composite::Capture(ref c)) => {
+                match (&c.remove_action, &c.move_action) {
+                    (Action::Remove(victim), Action::Move(mover, _, _)) => BbAction::Capture {
+                        victim: self.piece(victim, table)?,
+                        mover: self.piece(mover, table)?,
+                    },
+                    _ => return Err(String::from("malformed capture")),
+                }
+            },
+            Action::Pass(team) => BbAction::Pass(team as u8),
+            Action::Ally(_, _) | Action::Break(_, _) => return Err(String::from("positions do not track alliances")),
+            Action::Forfeit(_) => return Err(String::from("positions do not track eliminated teams")),
+        })
+    }
+
+    pub fn make(&mut self, action: BbAction) {
+        match action {
+            BbAction::Place(p) => {
+                self.pieces_left[p.team as usize] -= 1;
+                self.put(p);
+            },
+            BbAction::Remove(p) => self.take(p),
+            BbAction::Move(p, to) => {
+                self.take(p);
+                self.put(BbPiece { sq: to, ..p });
+            },
+            BbAction::Upgrade { sacrifice, old, new } => {
+                self.take(sacrifice);
+                self.take(old);
+                self.put(new);
+            },
+            BbAction::Capture { victim, mover } => {
+                self.take(victim);
+                self.take(mover);
+                self.put(BbPiece { sq: victim.sq, ..mover });
+            },
+            BbAction::Pass(_) => (),
+        }
+
+        self.next_turn();
+    }
+
+    pub fn unmake(&mut self, action: BbAction) {
+        match action {
+            BbAction::Place(p) => {
+                self.pieces_left[p.team as usize] += 1;
+                self.take(p);
+            },
+            BbAction::Remove(p) => self.put(p),
+            BbAction::Move(p, to) => {
+                self.take(BbPiece { sq: to, ..p });
+                self.put(p);
+            },
+            BbAction::Upgrade { sacrifice, old, new } => {
+                self.take(new);
+                self.put(sacrifice);
+                self.put(old);
+            },
+            BbAction::Capture { victim, mover } => {
+                self.take(BbPiece { sq: victim.sq, ..mover });
+                self.put(mover);
+                self.put(victim);
+            },
+            BbAction::Pass(_) => (),
+        }
+
+        self.turn = action.team();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, act, place};
+
+    #[test]
+    fn make_unmake_matches_game() {
+        let mut game = two_teams();
+        let mut table = MoveSetTable::new();
+
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 2);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 4);
+
+        let start = Position::from_game(&game, &mut table).unwrap();
+        let board = game.board.clone();
+        let mut pos = start;
+        let mut made = Vec::new();
+
+        for &(from, to) in &[((1, 0), (0, 0)), ((0, 2), (0, 1)), ((0, 0), (0, 1)), ((4, 4), (4, 3))] {
+            act(&mut game, from, to);
+
+            let action = game.action_stack.last().unwrap().clone();
+            let bb = pos.translate(&action, &mut table).unwrap();
+            pos.make(bb);
+            made.push(bb);
+
+            assert_eq!(pos, Position::from_game(&game, &mut table).unwrap());
+        }
+
+        for bb in made.into_iter().rev() {
+            pos.unmake(bb);
+        }
+        assert_eq!(pos, start);
+
+        // the pieces have moved on since, but each goes back with its own id
+        pos.write_to(&mut game, &table);
+        assert_eq!(game.board, board);
+        assert_eq!(game.turn, 0);
+    }
+
+    #[test]
+    fn too_big() {
+        let game = Game::from_vec(12, 11, Vec::new(), Vec::new());
+        assert!(Position::from_game(&game, &mut MoveSetTable::new()).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use game::pieces::Piece;
+use game::error::RuleError;
+
+/// The pieces in play, stored by square so that looking up a square is O(1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    width: u32, height: u32,
+    cells: Vec<Option<Piece>>,
+    // squares no piece can stand on
+    walls: Vec<bool>,
+}
+
+impl Board {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width, height,
+            cells: vec![None; (width * height) as usize],
+            walls: vec![false; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if self.in_bounds(x, y) {
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&Piece> {
+        self.index(x, y).and_then(|i| self.cells[i].as_ref())
+    }
+
+    /// whether the piece on the square of `piece` has its id
+    pub fn contains(&self, piece: &Piece) -> bool {
+        self.get(piece.x(), piece.y()).is_some_and(|p| p.id() == piece.id())
+    }
+
+    pub fn insert(&mut self, piece: Piece) -> Result<(), RuleError> {
+        let (x, y) = (piece.x(), piece.y());
+        let i = self.index(x, y).ok_or(RuleError::OutOfBounds(x, y))?;
+
+        if self.walls[i] {
+            return Err(RuleError::Wall(x, y));
+        }
+        if self.cells[i].is_some() {
+            return Err(RuleError::Occupied(x, y));
+        }
+
+        self.cells[i] = Some(piece);
+        Ok(())
+    }
+
+    /// removes `piece`, which must be the piece with its id on its square
+    pub fn remove(&mut self, piece: &Piece) -> Result<Piece, RuleError> {
+        if !self.contains(piece) {
+            return Err(RuleError::NoPiece(piece.x(), piece.y()));
+        }
+
+        self.remove_at(piece.x(), piece.y()).ok_or(RuleError::NoPiece(piece.x(), piece.y()))
+    }
+
+    pub fn remove_at(&mut self, x: i32, y: i32) -> Option<Piece> {
+        self.index(x, y).and_then(|i| self.cells[i].take())
+    }
+
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.walls[i])
+    }
+
+    /// builds or clears a wall, which can't go where there is a piece
+    pub fn set_wall(&mut self, x: i32, y: i32, wall: bool) -> Result<(), RuleError> {
+        let i = self.index(x, y).ok_or(RuleError::OutOfBounds(x, y))?;
+
+        if wall && self.cells[i].is_some() {
+            return Err(RuleError::Occupied(x, y));
+        }
+
+        self.walls[i] = wall;
+        Ok(())
+    }
+
+    /// every wall, row by row
+    pub fn walls(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let width = self.width as usize;
+        self.walls.iter().enumerate().filter(|&(_, &w)| w).map(move |(i, _)| ((i % width) as i32, (i / width) as i32))
+    }
+
+    /// every piece on the board, row by row
+    pub fn iter(&self) -> impl Iterator<Item = &Piece> {
+        self.cells.iter().filter_map(|cell| cell.as_ref())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::pieces::MoveDirSet;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut board = Board::new(4, 3);
+        let p = Piece::new(3, 2, 0).with_id(1);
+
+        board.insert(p.clone()).unwrap();
+        assert_eq!(board.get(3, 2), Some(&p));
+        assert_eq!(board.insert(Piece::new(3, 2, 1)), Err(RuleError::Occupied(3, 2)));
+        assert_eq!(board.insert(Piece::new(4, 0, 1)), Err(RuleError::OutOfBounds(4, 0)));
+
+        board.set_wall(1, 1, true).unwrap();
+        assert_eq!(board.insert(Piece::new(1, 1, 1)), Err(RuleError::Wall(1, 1)));
+        assert_eq!(board.set_wall(3, 2, true), Err(RuleError::Occupied(3, 2)));
+        assert_eq!(board.walls().collect::<Vec<_>>(), vec![(1, 1)]);
+        assert_eq!(board.iter().count(), 1);
+
+        // pieces are told apart by id, whatever else has changed about them
+        assert_eq!(board.remove(&p.clone().with_id(2)), Err(RuleError::NoPiece(3, 2)));
+        assert_eq!(board.remove(&Piece::from_parts(3, 2, 0, 2, MoveDirSet::new()).with_id(1)), Ok(p));
+        assert_eq!(board.iter().next(), None);
+        assert_eq!(board.get(-1, 0), None);
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::{Display, Formatter, Result};
+
+/// How much thinking time a team gets, all times are in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeControl {
+    Unlimited,
+    /// a fixed budget for the whole game
+    SuddenDeath(f64),
+    /// a budget for the whole game, plus `increment` added after every move
+    Fischer(f64, f64),
+    /// a budget for the whole game, up to `delay` of every move is given back
+    Bronstein(f64, f64),
+    /// every move must be made within the limit
+    PerMove(f64),
+}
+
+impl TimeControl {
+    fn initial(&self) -> f64 {
+        match *self {
+            TimeControl::Unlimited => f64::INFINITY,
+            TimeControl::SuddenDeath(total) |
+            TimeControl::Fischer(total, _) |
+            TimeControl::Bronstein(total, _) |
+            TimeControl::PerMove(total) => total,
+        }
+    }
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            TimeControl::Unlimited => write!(f, "unlimited"),
+            TimeControl::SuddenDeath(total) => write!(f, "sudden {}", total),
+            TimeControl::Fischer(total, inc) => write!(f, "fischer {} {}", total, inc),
+            TimeControl::Bronstein(total, delay) => write!(f, "bronstein {} {}", total, delay),
+            TimeControl::PerMove(limit) => write!(f, "per-move {}", limit),
+        }
+    }
+}
+
+/// What happens to a team whose time runs out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutPolicy {
+    /// the team is out of the game
+    Loss,
+    /// the team loses its turn, and the clock is reset as if it had moved
+    Pass,
+}
+
+impl Display for TimeoutPolicy {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            TimeoutPolicy::Loss => write!(f, "loss"),
+            TimeoutPolicy::Pass => write!(f, "pass"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timer {
+    pub control: TimeControl,
+    pub remaining: f64,
+}
+
+impl Timer {
+    pub fn new(control: TimeControl) -> Self {
+        Self { control, remaining: control.initial() }
+    }
+}
+
+/// One timer per team, only the timer of the team whose turn it is runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    pub policy: TimeoutPolicy,
+    timers: Vec<Timer>,
+    // time spent on the current move
+    spent: f64,
+}
+
+impl Clock {
+    pub fn new(policy: TimeoutPolicy, controls: Vec<TimeControl>) -> Self {
+        Self::from_timers(policy, controls.into_iter().map(Timer::new).collect(), 0.0)
+    }
+
+    pub fn from_timers(policy: TimeoutPolicy, timers: Vec<Timer>, spent: f64) -> Self {
+        Self { policy, timers, spent }
+    }
+
+    pub fn timers(&self) -> &[Timer] {
+        &self.timers
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.spent
+    }
+
+    /// runs `team`'s timer for `dt` seconds, returns true if its time ran out
+    pub fn tick(&mut self, team: usize, dt: f64) -> bool {
+        let timer = &mut self.timers[team];
+
+        if timer.control == TimeControl::Unlimited {
+            return false;
+        }
+
+        self.spent += dt;
+        timer.remaining -= dt;
+
+        timer.remaining <= 0.0
+    }
+
+    /// `team` finished its move, apply the increment or delay and reset the move timer
+    pub fn end_move(&mut self, team: usize) {
+        let spent = self.spent;
+        let timer = &mut self.timers[team];
+
+        match timer.control {
+            TimeControl::Unlimited |
+            TimeControl::SuddenDeath(_) => (),
+            TimeControl::Fischer(_, inc) => timer.remaining += inc,
+            TimeControl::Bronstein(_, delay) => timer.remaining += spent.min(delay),
+            TimeControl::PerMove(limit) => timer.remaining = limit,
+        }
+
+        self.spent = 0.0;
+    }
+}
+
+/// formats seconds as `m:ss`
+pub fn format_time(seconds: f64) -> String {
+    if seconds.is_infinite() {
+        String::from("--:--")
+    } else {
+        let seconds = seconds.max(0.0).ceil() as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn fischer() {
+        let mut clock = Clock::new(TimeoutPolicy::Loss, vec![TimeControl::Fischer(10.0, 2.0)]);
+
+        assert!(!clock.tick(0, 3.0));
+        clock.end_move(0);
+        assert_eq!(clock.timers()[0].remaining, 9.0);
+        assert!(clock.tick(0, 9.0));
+    }
+
+    #[test]
+    fn bronstein() {
+        let mut clock = Clock::new(TimeoutPolicy::Loss, vec![TimeControl::Bronstein(10.0, 2.0)]);
+
+        clock.tick(0, 1.0);
+        clock.end_move(0);
+        assert_eq!(clock.timers()[0].remaining, 10.0);
+
+        clock.tick(0, 5.0);
+        clock.end_move(0);
+        assert_eq!(clock.timers()[0].remaining, 7.0);
+    }
+
+    #[test]
+    fn per_move() {
+        let mut clock = Clock::new(TimeoutPolicy::Pass, vec![TimeControl::PerMove(5.0)]);
+
+        assert!(!clock.tick(0, 4.0));
+        clock.end_move(0);
+        assert!(!clock.tick(0, 4.0));
+        assert!(clock.tick(0, 1.0));
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut clock = Clock::new(TimeoutPolicy::Loss, vec![TimeControl::Unlimited]);
+
+        assert!(!clock.tick(0, 1e9));
+        assert_eq!(format_time(clock.timers()[0].remaining), "--:--");
+        assert_eq!(format_time(61.5), "1:02");
+    }
+}
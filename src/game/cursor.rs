@@ -0,0 +1,186 @@
+//! Plays the board without a mouse, with a cursor moved by the keyboard or a gamepad
+//!
+//! Arrows or WASD move the cursor, return or space clicks the square under it, P places
+//! there at once, X or backspace cancels the selection. On a gamepad the D-pad moves, A
+//! clicks, X places, B cancels and Y undoes. Holding a direction repeats it.
+
+use piston_window::*;
+
+use game::Game;
+
+// seconds a direction is held before it repeats, then between repeats
+const REPEAT_DELAY: f64 = 0.35;
+const REPEAT_INTERVAL: f64 = 0.08;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Move(i32, i32),
+    // the same as a left click on the cursor
+    Confirm,
+    Place,
+    Cancel,
+    Undo,
+}
+
+impl Command {
+    fn from_key(key: Key) -> Option<Command> {
+        match key {
+            Key::Up | Key::W => Some(Command::Move(0, -1)),
+            Key::Down | Key::S => Some(Command::Move(0, 1)),
+            Key::Left | Key::A => Some(Command::Move(-1, 0)),
+            Key::Right | Key::D => Some(Command::Move(1, 0)),
+            Key::Return | Key::Space => Some(Command::Confirm),
+            Key::P => Some(Command::Place),
+            Key::X | Key::Backspace => Some(Command::Cancel),
+            _ => None,
+        }
+    }
+
+    // buttons are numbered as in SDL's game controller layout
+    fn from_controller(button: ControllerButton) -> Option<Command> {
+        match button.button {
+            0 => Some(Command::Confirm),
+            1 => Some(Command::Cancel),
+            2 => Some(Command::Place),
+            3 => Some(Command::Undo),
+            11 => Some(Command::Move(0, -1)),
+            12 => Some(Command::Move(0, 1)),
+            13 => Some(Command::Move(-1, 0)),
+            14 => Some(Command::Move(1, 0)),
+            _ => None,
+        }
+    }
+
+    fn from_button(button: Button) -> Option<Command> {
+        match button {
+            Button::Keyboard(key) => Command::from_key(key),
+            Button::Controller(button) => Command::from_controller(button),
+            _ => None,
+        }
+    }
+}
+
+impl Game {
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let (width, height) = (self.board.width() as i32, self.board.height() as i32);
+        let (x, y) = self.cursor.unwrap_or((width as u32 / 2, height as u32 / 2));
+        let x = (x as i32 + dx).clamp(0, width - 1) as u32;
+        let y = (y as i32 + dy).clamp(0, height - 1) as u32;
+
+        self.cursor = Some((x, y));
+        self.hover = self.cursor;
+    }
+
+    fn command(&mut self, command: Command) {
+        if self.curtain {
+            if command == Command::Confirm {
+                self.lift_curtain();
+            }
+            return;
+        }
+
+        match command {
+            Command::Move(dx, dy) => self.move_cursor(dx, dy),
+            Command::Undo if !self.animator.is_busy() => self.undo_last(),
+            Command::Undo => (),
+            // a first press only shows the cursor where it would act
+            _ if self.cursor.is_none() => self.move_cursor(0, 0),
+            Command::Confirm | Command::Place => {
+                let (x, y) = self.cursor.unwrap_or_default();
+                if command == Command::Place {
+                    self.selected_x = x;
+                    self.selected_y = y;
+                }
+                self.click(MouseButton::Left, x, y);
+            },
+            Command::Cancel => {
+                self.selected_x = 100;
+                self.selected_y = 100;
+            },
+        }
+    }
+
+    pub fn wait_repeat(&mut self, dt: f64) {
+        if let Some((_, ref mut wait)) = self.repeat {
+            *wait -= dt;
+        }
+    }
+
+    /// runs the command of a button just pressed, starting the repeat of a direction
+    pub fn press(&mut self, button: Button) {
+        if let Some(command) = Command::from_button(button) {
+            self.command(command);
+            if let Command::Move(..) = command {
+                self.repeat = Some((button, REPEAT_DELAY));
+            }
+        }
+    }
+
+    /// repeats the direction of a button still held down once it is due
+    pub fn hold(&mut self, button: Button) {
+        match self.repeat {
+            Some((repeated, wait)) if repeated == button && wait <= 0.0 => {
+                self.repeat = Some((button, wait + REPEAT_INTERVAL));
+                if let Some(command) = Command::from_button(button) {
+                    self.command(command);
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::two_teams;
+
+    #[test]
+    fn plays_from_the_keyboard() {
+        let mut game = two_teams();
+        game.animator.enabled = false;
+
+        // the first move starts in the middle, and the cursor stays on the board
+        game.press(Button::Keyboard(Key::Up));
+        assert_eq!(game.cursor, Some((2, 1)));
+        for _ in 0..4 {
+            game.press(Button::Keyboard(Key::A));
+        }
+        game.press(Button::Keyboard(Key::Up));
+        assert_eq!(game.cursor, Some((0, 0)));
+
+        game.press(Button::Keyboard(Key::Return));
+        game.press(Button::Keyboard(Key::Return));
+        assert_eq!(game.get_piece(0, 0).map(|p| p.team()), Some(0));
+
+        game.press(Button::Keyboard(Key::S));
+        game.press(Button::Keyboard(Key::P));
+        assert_eq!(game.get_piece(0, 1).map(|p| p.team()), Some(1));
+
+        // select the first piece, then move it to the right
+        game.press(Button::Keyboard(Key::W));
+        game.press(Button::Keyboard(Key::Space));
+        game.press(Button::Keyboard(Key::D));
+        game.press(Button::Keyboard(Key::Space));
+        assert_eq!(game.get_piece(1, 0).map(|p| p.team()), Some(0));
+        assert!(game.get_piece(0, 0).is_none());
+
+        game.press(Button::Controller(ControllerButton { id: 0, button: 3 }));
+        assert_eq!(game.action_stack.len(), 2);
+    }
+
+    #[test]
+    fn held_directions_repeat() {
+        let mut game = two_teams();
+        let right = Button::Keyboard(Key::Right);
+
+        game.press(right);
+        game.hold(right);
+        assert_eq!(game.cursor, Some((3, 2)));
+
+        game.wait_repeat(REPEAT_DELAY);
+        game.hold(right);
+        game.hold(right);
+        assert_eq!(game.cursor, Some((4, 2)));
+    }
+}
@@ -0,0 +1,317 @@
+//! Builds starting positions by hand, saved as scenario files for `chess_like play --scenario`
+//!
+//! Click an empty square to place a piece of the chosen team, or a piece to select it, then
+//! click the squares of the grid below the board to change the directions it can move in.
+//! Right click deletes a piece, or builds and clears walls on empty squares.
+
+use std::path::Path;
+use std::sync::atomic::AtomicPtr;
+
+use piston_window::*;
+
+use gui::{App, Data, AppGraphics};
+use game::{Game, save, HUD_FONT_SIZE};
+use game::pieces::*;
+use game::board::Board;
+use game::theme::Theme;
+
+// height in pixels of the direction grid and the help below the board
+const FOOTER_HEIGHT: u32 = 130;
+const CELL: f64 = 20.0;
+const GRID_MARGIN: f64 = 12.0;
+// the grid shows the directions up to this many squares away
+const REACH: i32 = 2;
+const GRID: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+
+const MIN_SIZE: u32 = 3;
+const MAX_SIZE: u32 = 20;
+
+pub struct Editor {
+    game: Game,
+    path: String,
+    // the team new pieces belong to
+    team: usize,
+    selected: Option<(i32, i32)>,
+    message: String,
+}
+
+impl Editor {
+    pub fn new(mut game: Game, path: &str) -> Self {
+        game.footer = FOOTER_HEIGHT;
+        game.action_stack.clear();
+        game.redo_stack.clear();
+
+        Self {
+            game, path: String::from(path), team: 0, selected: None,
+            message: format!("S saves to {}", path),
+        }
+    }
+
+    /// edits the scenario at `path`, or a new one if there is nothing there yet
+    pub fn open(path: &str) -> Result<Self, String> {
+        let game = if Path::new(path).exists() { save::load_from_file(path)? } else { Game::new(10, 10) };
+
+        Ok(Editor::new(game, path))
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.game.theme = theme;
+        self
+    }
+
+    fn select(&mut self, square: Option<(i32, i32)>) {
+        self.selected = square;
+
+        let (x, y) = square.map(|(x, y)| (x as u32, y as u32)).unwrap_or((100, 100));
+        self.game.selected_x = x;
+        self.game.selected_y = y;
+    }
+
+    fn selected_piece(&self) -> Option<Piece> {
+        self.selected.and_then(|(x, y)| self.game.board.get(x, y).cloned())
+    }
+
+    // puts `piece` where the selected piece was, in its place and with its id
+    fn replace(&mut self, piece: Piece) {
+        let piece = match self.game.board.remove_at(piece.x(), piece.y()) {
+            Some(old) => piece.with_id(old.id()),
+            None => piece,
+        };
+        if let Err(msg) = self.game.board.insert(piece) {
+            println!("err = {}", msg);
+        }
+        self.game.forget_vision();
+    }
+
+    fn change_level(&mut self, delta: i32) {
+        if let Some(p) = self.selected_piece() {
+            let level = (p.level() as i32 + delta).clamp(1, self.game.rules.max_level() as i32) as u32;
+            self.replace(Piece::from_parts(p.x(), p.y(), p.team(), level, p.poss_moves.clone()));
+        }
+    }
+
+    fn toggle_direction(&mut self, dx: i32, dy: i32) {
+        if let Some(p) = self.selected_piece() {
+            let mut moves: Vec<(i32, i32)> = p.poss_moves.moves().iter().map(|m| (m.dx(), m.dy())).collect();
+
+            match moves.iter().position(|&m| m == (dx, dy)) {
+                Some(i) => { moves.remove(i); },
+                None => moves.push((dx, dy)),
+            }
+            self.replace(Piece::from_parts(p.x(), p.y(), p.team(), p.level(), MoveDirSet::from(moves)));
+        }
+    }
+
+    fn click_board(&mut self, button: MouseButton, x: u32, y: u32) {
+        let (ix, iy) = (x as i32, y as i32);
+
+        let result = match (button, self.game.board.get(ix, iy).is_some()) {
+            (MouseButton::Left, true) => Ok(()),
+            // the id is only used up once the piece is on the board, and not when a wall turns it away
+            (MouseButton::Left, false) => {
+                let piece = Piece::new(x as u16, y as u16, self.team).with_id(self.game.next_id);
+                self.game.board.insert(piece).map(|()| { self.game.new_id(); })
+            },
+            (MouseButton::Right, true) => {
+                self.game.board.remove_at(ix, iy);
+                Ok(())
+            },
+            (MouseButton::Right, false) => {
+                let wall = !self.game.board.is_wall(ix, iy);
+                self.game.board.set_wall(ix, iy, wall)
+            },
+            _ => Ok(()),
+        };
+        self.game.forget_vision();
+
+        match result {
+            Ok(()) if button == MouseButton::Left => self.select(Some((ix, iy))),
+            Ok(()) => self.select(None),
+            Err(msg) => self.message = msg.to_string(),
+        }
+    }
+
+    // a new board of the given size, keeping whatever still fits on it
+    fn resize(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.clamp(MIN_SIZE, MAX_SIZE), height.clamp(MIN_SIZE, MAX_SIZE));
+        let mut board = Board::new(width, height);
+
+        for (x, y) in self.game.board.walls() {
+            let _ = board.set_wall(x, y, true);
+        }
+        for p in self.game.board.iter() {
+            let _ = board.insert(p.clone());
+        }
+
+        self.game.board = board;
+        self.game.forget_vision();
+        self.select(None);
+    }
+
+    fn change_pieces_left(&mut self, delta: i32) {
+        let team = &mut self.game.teams[self.team];
+        team.pieces_left = (team.pieces_left as i32 + delta).max(0) as u32;
+    }
+
+    fn save(&mut self) {
+        self.message = match save::save_to_file(&self.game, &self.path) {
+            Ok(()) => format!("saved to {}", self.path),
+            Err(msg) => msg,
+        };
+    }
+
+    // the top left corner of the direction grid
+    fn grid_origin(&self) -> (f64, f64) {
+        (GRID_MARGIN, self.game.footer_top() + GRID_MARGIN)
+    }
+
+    // the direction of the grid cell under the cursor
+    fn grid_cell(&self, mouse_x: f64, mouse_y: f64) -> Option<(i32, i32)> {
+        let (left, top) = self.grid_origin();
+        let (dx, dy) = (((mouse_x - left) / CELL).floor() as i32 - REACH, ((mouse_y - top) / CELL).floor() as i32 - REACH);
+
+        if dx.abs() <= REACH && dy.abs() <= REACH && (dx, dy) != (0, 0) {
+            Some((dx, dy))
+        } else {
+            None
+        }
+    }
+}
+
+impl App for Editor {
+    fn set_data(&mut self, data: AtomicPtr<Data>) {
+        self.game.set_data(data);
+    }
+
+    fn set_window(&mut self, window: AtomicPtr<PistonWindow>) {
+        self.game.set_window(window);
+    }
+
+    fn render(&self, c: Context, g: &mut AppGraphics) {
+        use graphics::*;
+
+        self.game.render(c, g);
+
+        let (left, top) = self.grid_origin();
+        let piece = self.selected_piece();
+
+        for dy in -REACH..=REACH {
+            for dx in -REACH..=REACH {
+                let color = match piece {
+                    _ if (dx, dy) == (0, 0) => self.game.theme.text,
+                    Some(ref p) if p.can_move(MoveDir::new(dx, dy)) => self.game.team_color(p.team()),
+                    Some(_) => GRID,
+                    None => self.game.theme.selected,
+                };
+                let cell = [left + (dx + REACH) as f64 * CELL, top + (dy + REACH) as f64 * CELL, CELL - 2.0, CELL - 2.0];
+                rectangle(color, cell, c.transform, g);
+            }
+        }
+
+        let team = &self.game.teams[self.team];
+        let level = piece.map(|p| format!("level {} (- =)", p.level())).unwrap_or_else(|| String::from("no piece selected"));
+        let lines = [
+            format!("placing {} (1-9), {} left (, .), {} to move (tab)", team, team.pieces_left, self.game.teams[self.game.turn]),
+            format!("{}x{} board (arrows), {}", self.game.board.width(), self.game.board.height(), level),
+            String::from("right click deletes a piece or toggles a wall"),
+            self.message.clone(),
+        ];
+
+        let x = 2.0 * GRID_MARGIN + (2 * REACH + 1) as f64 * CELL;
+        for (i, line) in lines.iter().enumerate() {
+            let y = top + (i as f64 + 1.0) * (HUD_FONT_SIZE as f64 + 8.0) - 6.0;
+            self.game.draw_text(self.game.theme.text, line, x, y, c, g);
+        }
+    }
+
+    fn update(&mut self, _args: &UpdateArgs) {}
+
+    fn handle_key(&mut self, key: Key) {
+        let (width, height) = (self.game.board.width(), self.game.board.height());
+
+        match key {
+            Key::D1 | Key::D2 | Key::D3 | Key::D4 | Key::D5 | Key::D6 | Key::D7 | Key::D8 | Key::D9 => {
+                let team = key as usize - Key::D1 as usize;
+                if team < self.game.teams.len() {
+                    self.team = team;
+                }
+            },
+            Key::Tab => self.game.turn = (self.game.turn + 1) % self.game.teams.len(),
+            Key::Comma => self.change_pieces_left(-1),
+            Key::Period => self.change_pieces_left(1),
+            Key::Minus => self.change_level(-1),
+            Key::Equals => self.change_level(1),
+            Key::Left => self.resize(width.saturating_sub(1).max(1), height),
+            Key::Right => self.resize(width + 1, height),
+            Key::Up => self.resize(width, height.saturating_sub(1).max(1)),
+            Key::Down => self.resize(width, height + 1),
+            Key::S => self.save(),
+            _ => (),
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse_button: MouseButton, mouse_x: f64, mouse_y: f64) {
+        if let Some((x, y)) = self.game.tile_at(mouse_x, mouse_y) {
+            self.click_board(mouse_button, x, y);
+        } else if let Some((dx, dy)) = self.grid_cell(mouse_x, mouse_y) {
+            self.toggle_direction(dx, dy);
+        }
+    }
+
+    fn mouse_moved(&mut self, args: &Motion) {
+        self.game.mouse_moved(args);
+    }
+
+    fn handle_cursor(&mut self, cursor: bool) {
+        self.game.handle_cursor(cursor);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::two_teams;
+    use game::rules;
+
+    #[test]
+    fn builds_a_scenario() {
+        let mut editor = Editor::new(two_teams(), "unused");
+
+        editor.click_board(MouseButton::Left, 1, 1);
+        editor.change_level(2);
+        editor.toggle_direction(1, 0);
+        editor.toggle_direction(2, 2);
+        let p = editor.game.get_piece(1, 1).cloned().unwrap();
+        assert_eq!(p.level(), 3);
+        assert!(!p.can_move(MoveDir::new(1, 0)) && p.can_move(MoveDir::new(2, 2)));
+
+        editor.click_board(MouseButton::Right, 2, 2);
+        assert!(editor.game.board.is_wall(2, 2));
+        editor.click_board(MouseButton::Left, 2, 2);
+        assert!(editor.game.get_piece(2, 2).is_none());
+
+        editor.click_board(MouseButton::Left, 4, 4);
+        editor.resize(4, 4);
+        assert_eq!(editor.game.board.iter().count(), 1);
+
+        let loaded = save::read_game(&save::write_game(&editor.game)).unwrap();
+        assert_eq!(loaded.board, editor.game.board);
+    }
+
+    #[test]
+    fn edits_follow_the_rules() {
+        let mut editor = Editor::new(two_teams().with_rules(Box::new(rules::Strict)), "unused");
+
+        editor.click_board(MouseButton::Left, 1, 1);
+        editor.change_level(10);
+        assert_eq!(editor.game.get_piece(1, 1).unwrap().level(), 4);
+
+        // a piece turned away by a wall leaves the next id for the next piece
+        let next_id = editor.game.next_id;
+        editor.click_board(MouseButton::Right, 2, 2);
+        editor.click_board(MouseButton::Left, 2, 2);
+        assert_eq!(editor.game.next_id, next_id);
+        editor.click_board(MouseButton::Left, 3, 3);
+        assert_eq!(editor.game.get_piece(3, 3).unwrap().id(), next_id);
+    }
+}
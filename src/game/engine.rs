@@ -0,0 +1,292 @@
+//! Drives a seat with an external program, over a line based protocol on its stdin and stdout
+//!
+//! Lines sent to the engine (`>`) and read back from it (`<`):
+//!
+//! ```text
+//! > chess_like-engine 1
+//! < ready <name>                  the name is optional
+//! > position <n>                  then the n lines of the game, in the save file format
+//! > actions <n>                   then n lines `<index> <action>`, in the save file notation
+//! > go <team> <milliseconds>
+//! < play <index>                  or `resign`
+//! > quit
+//! ```
+//!
+//! Engines may send `info <text>` lines at any time, which are ignored. An engine that does
+//! not answer in time, answers with anything else or exits loses the game. The engine is
+//! talked to on a thread of its own, so the window keeps running while it thinks.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game::Game;
+use game::action::*;
+use game::player::Player;
+use game::save;
+
+const PROTOCOL: &str = "chess_like-engine 1";
+
+// the ends of the engine's pipes
+struct Pipe {
+    name: String,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+}
+
+// one turn for the engine: the lines that describe it, the actions on offer and where the
+// chosen one goes
+struct Request {
+    lines: Vec<String>,
+    actions: Vec<Action>,
+    reply: Sender<Result<Action, String>>,
+}
+
+pub struct EnginePlayer {
+    name: String,
+    child: Child,
+    // the time it has for each turn
+    millis: u64,
+    // gone once the player is dropped, which tells the worker to say quit
+    requests: Option<Sender<Request>>,
+}
+
+impl EnginePlayer {
+    /// starts `program` and waits up to `timeout` for it to say it is ready
+    pub fn new(program: &str, args: &[&str], timeout: Duration) -> Result<Self, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not start {}: {}", program, e))?;
+
+        let stdin = child.stdin.take().ok_or("the engine has no stdin")?;
+        let stdout = child.stdout.take().ok_or("the engine has no stdout")?;
+        let (send, lines) = channel();
+
+        // reads on a separate thread, so that waiting for the engine can time out
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if send.send(line).is_err() { break },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        let mut pipe = Pipe { name: String::from(program), stdin, lines, timeout };
+        let ready = pipe.send(PROTOCOL).and_then(|_| pipe.receive());
+        let reply = match ready {
+            Ok(reply) => reply,
+            Err(msg) => return Err(stop(child, msg)),
+        };
+
+        let mut tokens = reply.split_whitespace();
+        if tokens.next() != Some("ready") {
+            return Err(stop(child, format!("{} did not say ready, it said {:?}", program, reply)));
+        }
+        let name: Vec<&str> = tokens.collect();
+        if !name.is_empty() {
+            pipe.name = name.join(" ");
+        }
+
+        let name = pipe.name.clone();
+        let millis = timeout.as_secs() * 1000 + timeout.subsec_millis() as u64;
+        let (requests, work) = channel::<Request>();
+        thread::spawn(move || {
+            for request in work {
+                let _ = request.reply.send(pipe.play(request.lines, request.actions));
+            }
+            let _ = pipe.send("quit");
+        });
+
+        Ok(Self { name, child, millis, requests: Some(requests) })
+    }
+
+    /// parses `command` the way a shell would split it on spaces, without any quoting
+    pub fn from_command(command: &str, timeout: Duration) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("the engine command is empty")?;
+        let args: Vec<&str> = words.collect();
+
+        EnginePlayer::new(program, &args, timeout)
+    }
+}
+
+// ends an engine that never got going
+fn stop(mut child: Child, msg: String) -> String {
+    let _ = child.kill();
+    let _ = child.wait();
+    msg
+}
+
+impl Pipe {
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("{} stopped listening: {}", self.name, e))
+    }
+
+    // the next line that isn't `info`
+    fn receive(&mut self) -> Result<String, String> {
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            let left = deadline.checked_duration_since(Instant::now()).unwrap_or_default();
+
+            match self.lines.recv_timeout(left) {
+                Ok(ref line) if line.starts_with("info") => continue,
+                Ok(line) => return Ok(line),
+                Err(RecvTimeoutError::Timeout) => return Err(format!("{} ran out of time", self.name)),
+                Err(RecvTimeoutError::Disconnected) => return Err(format!("{} exited", self.name)),
+            }
+        }
+    }
+
+    // sends one turn and reads back the engine's choice
+    fn play(&mut self, lines: Vec<String>, mut actions: Vec<Action>) -> Result<Action, String> {
+        // a reply that came in after its turn timed out would otherwise answer this one
+        while self.lines.try_recv().is_ok() {}
+
+        for line in lines {
+            self.send(&line)?;
+        }
+
+        let reply = self.receive()?;
+        let mut tokens = reply.split_whitespace();
+
+        match (tokens.next(), tokens.next().and_then(|i| i.parse::<usize>().ok())) {
+            (Some("play"), Some(i)) if i < actions.len() => Ok(actions.swap_remove(i)),
+            (Some("resign"), _) => Err(format!("{} resigned", self.name)),
+            _ => Err(format!("{} sent {:?} instead of a legal action", self.name, reply)),
+        }
+    }
+}
+
+impl Player for EnginePlayer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose(&mut self, game: &Game) -> Result<Action, String> {
+        match self.choose_later(game) {
+            Some(answer) => answer.recv().unwrap_or_else(|_| Err(format!("{} exited", self.name))),
+            None => Err(format!("{} exited", self.name)),
+        }
+    }
+
+    fn choose_later(&mut self, game: &Game) -> Option<Receiver<Result<Action, String>>> {
+        let position = save::write_game(game);
+        let actions = game.legal_actions();
+
+        let mut lines = vec![format!("position {}", position.lines().count())];
+        lines.extend(position.lines().map(String::from));
+        lines.push(format!("actions {}", actions.len()));
+        lines.extend(actions.iter().enumerate().map(|(i, action)| format!("{} {}", i, save::write_action(action))));
+        lines.push(format!("go {} {}", game.turn(), self.millis));
+
+        // a worker that has stopped drops the reply, which reads as the engine exiting
+        let (reply, answer) = channel();
+        if let Some(ref requests) = self.requests {
+            let _ = requests.send(Request { lines, actions, reply });
+        }
+        Some(answer)
+    }
+}
+
+impl Drop for EnginePlayer {
+    fn drop(&mut self) {
+        self.requests = None;
+
+        // give the engine a moment to exit on its own
+        let deadline = Instant::now() + Duration::from_millis(200);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::two_teams;
+
+    fn engine(script: &str, timeout: u64) -> Result<EnginePlayer, String> {
+        EnginePlayer::new("sh", &["-c", script], Duration::from_millis(timeout))
+    }
+
+    const FIRST: &str = "read hello; echo ready first bot; \
+                         while read line; do case \"$line\" in go*) echo info thinking; echo play 0;; quit) exit;; esac; done";
+
+    #[test]
+    fn plays_the_chosen_action() {
+        let game = two_teams();
+        let mut first = engine(FIRST, 2000).unwrap();
+
+        assert_eq!(first.name(), "first bot");
+        let action = first.choose(&game).unwrap();
+        assert_eq!(format!("{:?}", action), format!("{:?}", game.legal_actions()[0]));
+    }
+
+    #[test]
+    fn the_game_runs_on_while_an_engine_thinks() {
+        let slow = "read hello; echo ready; \
+                    while read line; do case \"$line\" in go*) sleep 0.3; echo play 0;; quit) exit;; esac; done";
+        let mut game = two_teams().with_bot(0, Box::new(engine(slow, 2000).unwrap()));
+
+        let asked = Instant::now();
+        game.play_bot();
+        assert!(asked.elapsed() < Duration::from_millis(200));
+        assert!(game.action_stack.is_empty() && game.thinking.is_some());
+
+        for _ in 0..200 {
+            game.play_bot();
+            if !game.action_stack.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(game.action_stack.len(), 1);
+        assert_eq!(game.turn(), 1);
+    }
+
+    #[test]
+    fn misbehaving_engines_lose() {
+        let game = two_teams();
+
+        assert!(engine("read hello; echo hi", 2000).is_err());
+        assert!(engine("read hello; sleep 5", 100).is_err());
+
+        let mut silent = engine("read hello; echo ready; sleep 5", 100).unwrap();
+        assert!(silent.choose(&game).unwrap_err().contains("ran out of time"));
+
+        let mut wrong = engine("read hello; echo ready; while read line; do case \"$line\" in go*) echo play 9999;; esac; done", 2000).unwrap();
+        assert!(wrong.choose(&game).is_err());
+
+        let mut quits = engine("read hello; echo ready", 2000).unwrap();
+        assert!(quits.choose(&game).is_err());
+    }
+
+    #[test]
+    fn late_replies_are_dropped() {
+        let game = two_teams();
+        let late = "read hello; echo ready; n=0; \
+                    while read line; do case \"$line\" in go*) n=$((n + 1)); \
+                    if [ $n = 1 ]; then sleep 0.3; echo play 1; else echo play 0; fi;; quit) exit;; esac; done";
+        let mut late = engine(late, 100).unwrap();
+
+        assert!(late.choose(&game).unwrap_err().contains("ran out of time"));
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(late.choose(&game).unwrap().squares(), game.legal_actions()[0].squares());
+    }
+}
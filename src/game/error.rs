@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result};
+
+/// Why the rules refused an action
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleError {
+    /// the square is outside of the board
+    OutOfBounds(i32, i32),
+    /// there is already a piece on the square
+    Occupied(i32, i32),
+    /// the square is a wall
+    Wall(i32, i32),
+    /// the piece expected on the square is not there
+    NoPiece(i32, i32),
+    /// the team has no pieces left to place
+    OutOfPlacements(usize),
+    /// the square is not in any of the team's placement zones
+    OutsidePlacementZone(usize),
+    /// the team tried to act while it was another team's turn
+    NotYourTurn(usize),
+    /// the piece cannot move by `(dx, dy)`
+    IllegalDirection(i32, i32),
+    /// merging would make a piece of this level, which is over the cap
+    LevelTooHigh(u32),
+    /// merging found no new direction for one of the sacrificed piece's directions
+    NoNewDirection,
+    /// a piece of the first level may not capture a piece of the second level
+    CaptureNotAllowed(u32, u32),
+    /// the piece belongs to an ally of the team
+    CaptureAlly(usize),
+    /// the team has already offered an alliance to this team
+    AlreadyOffered(usize),
+    /// the team has not offered an alliance to this team, so there is nothing to break
+    NotOffered(usize),
+    /// the action is not shaped the way the rules expect, e.g. a capture that does not land on its victim
+    MalformedAction(&'static str),
+    /// the game has already been decided
+    GameOver,
+    /// there is no undone action to redo
+    NothingToRedo,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            RuleError::OutOfBounds(x, y) => write!(f, "({}, {}) is not on the board", x, y),
+            RuleError::Occupied(x, y) => write!(f, "there is already a piece at ({}, {})", x, y),
+            RuleError::Wall(x, y) => write!(f, "there is a wall at ({}, {})", x, y),
+            RuleError::NoPiece(x, y) => write!(f, "no piece at ({}, {})", x, y),
+            RuleError::OutOfPlacements(team) => write!(f, "team {} cannot place anymore pieces", team + 1),
+            RuleError::OutsidePlacementZone(team) =>
+                write!(f, "team {} cannot place outside of its placement zone", team + 1),
+            RuleError::NotYourTurn(team) => write!(f, "it is not team {}'s turn", team + 1),
+            RuleError::IllegalDirection(dx, dy) => write!(f, "the piece cannot move by ({}, {})", dx, dy),
+            RuleError::LevelTooHigh(level) => write!(f, "level {} is too high to control", level),
+            RuleError::NoNewDirection => write!(f, "merging these pieces gives no new direction"),
+            RuleError::CaptureNotAllowed(attacker, defender) =>
+                write!(f, "a level {} piece cannot capture a level {} piece", attacker, defender),
+            RuleError::CaptureAlly(team) => write!(f, "team {} is an ally", team + 1),
+            RuleError::AlreadyOffered(team) => write!(f, "an alliance was already offered to team {}", team + 1),
+            RuleError::NotOffered(team) => write!(f, "no alliance was offered to team {}", team + 1),
+            RuleError::MalformedAction(why) => write!(f, "malformed action: {}", why),
+            RuleError::GameOver => write!(f, "the game is over"),
+            RuleError::NothingToRedo => write!(f, "nothing to redo"),
+        }
+    }
+}
+
+impl Error for RuleError {}
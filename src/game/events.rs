@@ -0,0 +1,177 @@
+//! What happens in a game, told to whoever subscribed to it
+//!
+//! Every action the game commits is broken down into events, followed by the turn passing
+//! on unless it was diplomacy and, if that ended the game, the winners. Undoing an action
+//! tells its observers what was undone, and replacing the whole game, like loading a save,
+//! tells them it was loaded. Observers are called in the order they subscribed, after the
+//! board has changed.
+
+use std::fmt::{Display, Formatter, Result};
+use std::fs::File;
+use std::io::{self, Write};
+
+use game::Game;
+use game::action::*;
+use game::pieces::{Piece, Move, MoveDir};
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Placed(Piece),
+    Removed(Piece),
+    // the piece before and after it moved
+    Moved(Piece, Piece),
+    // the piece taken, and the piece that took it where it ends up
+    Captured(Piece, Piece),
+    // the piece sacrificed, the piece it merged into and what that became
+    Upgraded(Piece, Piece, Piece),
+    // the team that passed, the team that offered an alliance or withdrew its offer and to
+    // whom, and the team that is out of the game
+    Passed(usize),
+    Offered(usize, usize),
+    Withdrew(usize, usize),
+    Eliminated(usize),
+    // the team to move next
+    Turn(usize),
+    // the action taken back, which was the last one of the history
+    Undone(Action),
+    // the teams that share the victory
+    GameOver(Vec<usize>),
+    // the game was replaced by another one
+    Loaded,
+}
+
+impl Event {
+    /// the events of `action`, once it has been applied
+    pub fn of(action: &Action) -> Vec<Event> {
+        match *action {
+            Action::Place(ref p) => vec![Event::Placed(p.clone())],
+            Action::Remove(ref p) => vec![Event::Removed(p.clone())],
+            Action::Move(ref p, dx, dy) => vec![Event::Moved(p.clone(), moved(p, dx, dy))],
+            Action::Upgrade(ref sacrifice, ref old, ref new) =>
+                vec![Event::Upgraded(sacrifice.clone(), old.clone(), new.clone())],
+            Action::Composite(Composite::Capture(ref c)) => match (&c.remove_action, &c.move_action) {
+                (Action::Remove(victim), Action::Move(p, dx, dy)) => {
+                    let to = moved(p, *dx, *dy);
+                    vec![Event::Captured(victim.clone(), to.clone()), Event::Moved(p.clone(), to)]
+                },
+                _ => Vec::new(),
+            },
+            Action::Pass(team) => vec![Event::Passed(team)],
+            Action::Ally(team, other) => vec![Event::Offered(team, other)],
+            Action::Break(team, other) => vec![Event::Withdrew(team, other)],
+            Action::Forfeit(team) => vec![Event::Eliminated(team)],
+        }
+    }
+}
+
+fn moved(p: &Piece, dx: i32, dy: i32) -> Piece {
+    let mut to = p.clone();
+    to.apply(Move::from(MoveDir::new(dx, dy)));
+    to
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            Event::Placed(ref p) => write!(f, "placed {}", p),
+            Event::Removed(ref p) => write!(f, "removed #{}", p.id()),
+            Event::Moved(ref from, ref to) =>
+                write!(f, "moved #{} ({}, {}) -> ({}, {})", from.id(), from.x(), from.y(), to.x(), to.y()),
+            Event::Captured(ref victim, ref by) => write!(f, "captured #{} by #{}", victim.id(), by.id()),
+            Event::Upgraded(ref sacrifice, ref old, ref new) =>
+                write!(f, "upgraded #{} from level {} to {} with #{}", new.id(), old.level(), new.level(), sacrifice.id()),
+            Event::Passed(team) => write!(f, "team {} passed", team + 1),
+            Event::Offered(team, other) => write!(f, "team {} offered an alliance to team {}", team + 1, other + 1),
+            Event::Withdrew(team, other) => write!(f, "team {} withdrew its offer to team {}", team + 1, other + 1),
+            Event::Eliminated(team) => write!(f, "team {} is out", team + 1),
+            Event::Turn(team) => write!(f, "turn {}", team + 1),
+            Event::Undone(ref action) => write!(f, "undone {:?}", action),
+            Event::GameOver(ref winners) => {
+                let winners: Vec<String> = winners.iter().map(|t| (t + 1).to_string()).collect();
+                write!(f, "game over, won by {}", winners.join(" "))
+            },
+            Event::Loaded => write!(f, "loaded"),
+        }
+    }
+}
+
+/// Something told about every event of a game, with the game as it is afterwards
+pub trait Observer {
+    fn notify(&mut self, game: &Game, event: &Event);
+}
+
+impl<F: FnMut(&Game, &Event)> Observer for F {
+    fn notify(&mut self, game: &Game, event: &Event) {
+        self(game, event)
+    }
+}
+
+/// Writes one line per event
+pub struct Log {
+    out: Box<dyn Write>,
+}
+
+impl Log {
+    /// a log written to the file at `path`, or to the terminal for `-`
+    pub fn open(path: &str) -> std::result::Result<Self, String> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path).map_err(|e| format!("could not create {}: {}", path, e))?)
+        };
+
+        Ok(Self { out })
+    }
+}
+
+impl Observer for Log {
+    fn notify(&mut self, game: &Game, event: &Event) {
+        let written = match *event {
+            Event::Turn(team) => writeln!(self.out, "{} ({})", event, game.teams()[team]),
+            _ => writeln!(self.out, "{}", event),
+        };
+
+        if let Err(e) = written.and_then(|_| self.out.flush()) {
+            println!("err = could not log an event: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use game::test::two_teams;
+
+    #[test]
+    fn observers_hear_actions_turns_and_undos() {
+        let heard = Rc::new(RefCell::new(Vec::new()));
+        let log = heard.clone();
+        let mut game = two_teams().with_observer(Box::new(move |_: &Game, e: &Event| log.borrow_mut().push(e.to_string())));
+        game.animator.enabled = false;
+
+        let place = game.place(0, 0, 0).unwrap();
+        game.do_action(place).unwrap();
+        game.undo_last();
+        game.redo_last().unwrap();
+        game.do_action(Action::Move(game.get_piece(0, 0).cloned().unwrap(), 1, 1)).unwrap_err();
+        let placed = "placed piece #1 @(0, 0, 0) -> { $(1, 0), $(-1, 0), $(0, 1), $(0, -1) }";
+        assert_eq!(*heard.borrow(), vec![
+            placed, "turn 2",
+            "undone Place", "turn 1",
+            placed, "turn 2",
+        ]);
+
+        // nothing is heard of actions that were refused, and diplomacy keeps the turn
+        heard.borrow_mut().clear();
+        game.do_action(Action::Ally(1, 0)).unwrap();
+        game.do_action(Action::Pass(1)).unwrap();
+        game.do_action(Action::Ally(0, 1)).unwrap();
+        assert_eq!(heard.borrow()[..], [
+            "team 2 offered an alliance to team 1",
+            "team 2 passed", "turn 1",
+            "team 1 offered an alliance to team 2", "game over, won by 1 2",
+        ]);
+    }
+}
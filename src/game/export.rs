@@ -0,0 +1,259 @@
+//! Draws a board without a window, as an SVG document or a software rasterized PNG
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::fmt::Display;
+
+use png;
+use png::HasParameters;
+
+use game::Game;
+use game::pips::{Mark, BODY, level_marks};
+
+/// Something the board can be painted onto, in pixels
+pub trait Canvas {
+    fn rect(&mut self, color: [f32; 4], rect: [f64; 4]);
+    fn ellipse(&mut self, color: [f32; 4], rect: [f64; 4]);
+    fn polygon(&mut self, color: [f32; 4], corners: &[[f64; 2]]);
+}
+
+impl Game {
+    /// paints the tiles and pieces the way `render` does, with every tile `tile` pixels wide
+    pub fn paint<C: Canvas>(&self, canvas: &mut C, tile: f64) {
+        for i in 0..self.board.width() {
+            for j in 0..self.board.height() {
+                canvas.rect(self.tile_color(i, j, None), [i as f64 * tile, j as f64 * tile, tile, tile]);
+            }
+        }
+
+        for p in self.board.iter() {
+            let color = self.team_color(p.team());
+            let (x, y) = (p.x() as f64, p.y() as f64);
+            let scaled = |r: [f64; 4]| [(x + r[0]) * tile, (y + r[1]) * tile, r[2] * tile, r[3] * tile];
+
+            match self.theme.marker(p.team()).corners(scaled(BODY)) {
+                Some(corners) => canvas.polygon(color, &corners),
+                None => canvas.ellipse(color, scaled(BODY)),
+            }
+
+            for mark in level_marks(p.level()) {
+                match mark {
+                    Mark::Pip(r) => canvas.ellipse(color, scaled(r)),
+                    Mark::Bar(r) => canvas.rect(self.theme.text, scaled(r)),
+                }
+            }
+        }
+    }
+
+    /// the size in pixels of the board when painted with `tile` pixel tiles
+    pub fn paint_size(&self, tile: f64) -> (f64, f64) {
+        (self.board.width() as f64 * tile, self.board.height() as f64 * tile)
+    }
+}
+
+pub struct Svg {
+    body: String,
+}
+
+fn svg_color(color: [f32; 4]) -> String {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    format!("fill=\"rgb({},{},{})\" fill-opacity=\"{}\"", c(color[0]), c(color[1]), c(color[2]), color[3])
+}
+
+impl Svg {
+    pub fn new() -> Self {
+        Self { body: String::new() }
+    }
+
+    pub fn finish(self, width: f64, height: f64) -> String {
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n{2}</svg>\n",
+                width, height, self.body)
+    }
+}
+
+impl Canvas for Svg {
+    fn rect(&mut self, color: [f32; 4], r: [f64; 4]) {
+        self.body += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>\n",
+                              r[0], r[1], r[2], r[3], svg_color(color));
+    }
+
+    fn ellipse(&mut self, color: [f32; 4], r: [f64; 4]) {
+        self.body += &format!("<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" {}/>\n",
+                              r[0] + r[2] / 2.0, r[1] + r[3] / 2.0, r[2] / 2.0, r[3] / 2.0, svg_color(color));
+    }
+
+    fn polygon(&mut self, color: [f32; 4], corners: &[[f64; 2]]) {
+        let points: Vec<String> = corners.iter().map(|c| format!("{},{}", c[0], c[1])).collect();
+        self.body += &format!("<polygon points=\"{}\" {}/>\n", points.join(" "), svg_color(color));
+    }
+}
+
+// samples per pixel along each axis, for anti-aliasing
+const SAMPLES: u32 = 4;
+
+/// An RGBA image on a white background
+pub struct Raster {
+    width: u32, height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, pixels: vec![[1.0; 4]; (width * height) as usize] }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let p = self.pixels[(y * self.width + x) as usize];
+        let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        [c(p[0]), c(p[1]), c(p[2]), c(p[3])]
+    }
+
+    // blends `color` over every pixel in `rect`, weighted by how many samples `inside` accepts
+    fn fill<F: Fn(f64, f64) -> bool>(&mut self, color: [f32; 4], rect: [f64; 4], inside: F) {
+        let x0 = rect[0].floor().max(0.0) as u32;
+        let y0 = rect[1].floor().max(0.0) as u32;
+        let x1 = ((rect[0] + rect[2]).ceil().max(0.0) as u32).min(self.width);
+        let y1 = ((rect[1] + rect[3]).ceil().max(0.0) as u32).min(self.height);
+        let step = 1.0 / SAMPLES as f64;
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let mut hits = 0;
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let (px, py) = (x as f64 + (sx as f64 + 0.5) * step, y as f64 + (sy as f64 + 0.5) * step);
+                        hits += inside(px, py) as u32;
+                    }
+                }
+
+                let a = color[3] * hits as f32 / (SAMPLES * SAMPLES) as f32;
+                let pixel = &mut self.pixels[(y * self.width + x) as usize];
+                for i in 0..3 {
+                    pixel[i] = color[i] * a + pixel[i] * (1.0 - a);
+                }
+                pixel[3] = a + pixel[3] * (1.0 - a);
+            }
+        }
+    }
+
+    pub fn write_png(&self, path: &str) -> Result<(), String> {
+        let err = |e: &dyn Display| format!("could not write {}: {}", path, e);
+        let file = File::create(path).map_err(|e| err(&e))?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                data.extend_from_slice(&self.pixel(x, y));
+            }
+        }
+
+        let mut writer = encoder.write_header().map_err(|e| err(&e))?;
+        writer.write_image_data(&data).map_err(|e| err(&e))
+    }
+}
+
+impl Canvas for Raster {
+    fn rect(&mut self, color: [f32; 4], r: [f64; 4]) {
+        self.fill(color, r, |x, y| x >= r[0] && y >= r[1] && x < r[0] + r[2] && y < r[1] + r[3]);
+    }
+
+    fn ellipse(&mut self, color: [f32; 4], r: [f64; 4]) {
+        let (rx, ry) = (r[2] / 2.0, r[3] / 2.0);
+        let (cx, cy) = (r[0] + rx, r[1] + ry);
+
+        self.fill(color, r, |x, y| {
+            let (dx, dy) = ((x - cx) / rx, (y - cy) / ry);
+            dx * dx + dy * dy <= 1.0
+        });
+    }
+
+    // markers are convex, so a point inside is on the same side of every edge
+    fn polygon(&mut self, color: [f32; 4], corners: &[[f64; 2]]) {
+        let (xs, ys) = (corners.iter().map(|c| c[0]), corners.iter().map(|c| c[1]));
+        let (left, top) = (xs.clone().fold(f64::MAX, f64::min), ys.clone().fold(f64::MAX, f64::min));
+        let (right, bottom) = (xs.fold(f64::MIN, f64::max), ys.fold(f64::MIN, f64::max));
+
+        self.fill(color, [left, top, right - left, bottom - top], |x, y| {
+            let sides: Vec<f64> = corners.iter().zip(corners.iter().cycle().skip(1))
+                .map(|(a, b)| (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0]))
+                .collect();
+            sides.iter().all(|&s| s >= 0.0) || sides.iter().all(|&s| s <= 0.0)
+        });
+    }
+}
+
+/// writes `game` to `path`, as a PNG if it ends in `.png` and as an SVG otherwise
+pub fn export(game: &Game, path: &str, tile: f64) -> Result<(), String> {
+    let (width, height) = game.paint_size(tile);
+
+    if path.ends_with(".png") {
+        let mut raster = Raster::new(width.ceil() as u32, height.ceil() as u32);
+        game.paint(&mut raster, tile);
+        raster.write_png(path)
+    } else {
+        let mut svg = Svg::new();
+        game.paint(&mut svg, tile);
+
+        File::create(path)
+            .and_then(|mut file| file.write_all(svg.finish(width, height).as_bytes()))
+            .map_err(|e| format!("could not write {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, place};
+    use game::theme::Theme;
+
+    #[test]
+    fn svg_has_every_tile_and_pip() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+
+        let mut svg = Svg::new();
+        game.paint(&mut svg, 10.0);
+        let svg = svg.finish(50.0, 50.0);
+
+        assert_eq!(svg.matches("<rect").count(), 25);
+        assert_eq!(svg.matches("<ellipse").count(), 1);
+        assert!(svg.starts_with("<svg") && svg.contains("viewBox=\"0 0 50 50\""));
+    }
+
+    #[test]
+    fn raster_matches_the_colors() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+
+        let mut raster = Raster::new(50, 50);
+        game.paint(&mut raster, 10.0);
+
+        let c = |color: [f32; 4]| [0, 1, 2, 3].map(|i| (color[i] * 255.0).round() as u8);
+        // the middle of the piece, and an empty tile
+        assert_eq!(raster.pixel(5, 5), c(game.teams[0].color));
+        assert_eq!(raster.pixel(15, 45), c(game.tile_color(1, 4, None)));
+    }
+
+    #[test]
+    fn themes_recolor_and_reshape_pieces() {
+        let theme = Theme::colorblind();
+        let mut game = two_teams().with_theme(theme.clone());
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+
+        let mut svg = Svg::new();
+        game.paint(&mut svg, 10.0);
+        let svg = svg.finish(50.0, 50.0);
+        assert_eq!((svg.matches("<ellipse").count(), svg.matches("<polygon").count()), (1, 1));
+
+        let mut raster = Raster::new(50, 50);
+        game.paint(&mut raster, 10.0);
+        let c = |color: [f32; 4]| [0, 1, 2, 3].map(|i| (color[i] * 255.0).round() as u8);
+        assert_eq!(raster.pixel(45, 45), c(theme.teams[1]));
+    }
+}
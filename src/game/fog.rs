@@ -0,0 +1,258 @@
+//! Hidden information: each team only sees the squares around its own and its allies' pieces
+
+use std::fmt::{Display, Formatter, Result};
+use std::sync::mpsc::Receiver;
+
+use game::{Game, action::Action};
+use game::player::Player;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vision {
+    // the squares the pieces could move to
+    Reach,
+    // every square within this many steps, diagonals included
+    Radius(u32),
+}
+
+impl Vision {
+    /// `reach`, or a radius
+    pub fn parse(s: &str) -> ::std::result::Result<Self, String> {
+        match s {
+            "reach" => Ok(Vision::Reach),
+            _ => s.parse().map(Vision::Radius)
+                  .map_err(|_| format!("fog must be `reach` or a radius, not `{}`", s)),
+        }
+    }
+}
+
+impl Display for Vision {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            Vision::Reach => write!(f, "reach"),
+            Vision::Radius(r) => write!(f, "{}", r),
+        }
+    }
+}
+
+impl Game {
+    pub fn with_fog(mut self, vision: Vision) -> Self {
+        self.fog = Some(vision);
+        self
+    }
+
+    /// the squares `team` can see, row by row, or `None` without fog
+    pub fn vision(&self, team: usize) -> Option<Vec<bool>> {
+        let vision = self.fog?;
+        let (width, height) = (self.board.width() as i32, self.board.height() as i32);
+        let mut seen = vec![false; (width * height) as usize];
+        let mut see = |x: i32, y: i32| if self.board.in_bounds(x, y) {
+            seen[(y * width + x) as usize] = true;
+        };
+
+        for p in self.board.iter().filter(|p| p.team() == team || self.allied(team, p.team())) {
+            see(p.x(), p.y());
+
+            match vision {
+                Vision::Reach => for m in p.poss_moves.moves() {
+                    see(p.x() + m.dx(), p.y() + m.dy());
+                },
+                Vision::Radius(r) => {
+                    let r = r as i32;
+                    for dy in -r..=r {
+                        for dx in -r..=r {
+                            see(p.x() + dx, p.y() + dy);
+                        }
+                    }
+                },
+            }
+        }
+
+        // a team always knows where it may place
+        let alliance = &self.teams[team];
+        if !alliance.zones.is_empty() {
+            for y in 0..height {
+                for x in 0..width {
+                    if alliance.can_place_at(x as u32, y as u32, team, &self.board) {
+                        see(x, y);
+                    }
+                }
+            }
+        }
+
+        Some(seen)
+    }
+
+    /// whether `team` sees (x, y), working out its vision only once for each change to the board
+    pub fn sees(&self, team: usize, x: u32, y: u32) -> bool {
+        let vision = match self.fog {
+            Some(vision) => vision,
+            None => return true,
+        };
+
+        let mut seen = self.seen.borrow_mut();
+        if seen.as_ref().is_none_or(|&(t, v, _)| (t, v) != (team, vision)) {
+            *seen = self.vision(team).map(|squares| (team, vision, squares));
+        }
+        seen.as_ref().and_then(|(_, _, squares)| squares.get((y * self.board.width() + x) as usize)) == Some(&true)
+    }
+
+    /// the game as `team` sees it, without the pieces it can't see or any of the history
+    pub fn view(&self, team: usize) -> Game {
+        let vision = self.vision(team);
+        let width = self.board.width();
+        let pieces = self.board.iter()
+            .filter(|p| vision.as_ref().is_none_or(|seen| seen[(p.y() as u32 * width + p.x() as u32) as usize]))
+            .cloned()
+            .collect();
+        let mut view = Game::from_vec(width, self.board.height(), pieces, self.teams.clone())
+            .with_clock(self.clock.clone());
+        view.rules = self.rules.clone();
+        view.turn = self.turn;
+        view.offers = self.offers.clone();
+        view.fog = self.fog;
+        view.seed = self.seed;
+        // the fog hides pieces, not the lie of the land
+        for (x, y) in self.board.walls() {
+            let _ = view.board.set_wall(x, y, true);
+        }
+        view
+    }
+
+    /// an action chosen on a view, as it turns out on the real board: moving onto a hidden
+    /// piece captures it if it can, upgrades roll against the real history, and an action
+    /// the hidden pieces block becomes a pass
+    pub fn resolve(&self, action: Action) -> Action {
+        let resolved = match action {
+            Action::Move(ref p, dx, dy) => {
+                let (x, y) = (p.x() + dx, p.y() + dy);
+                if self.board.in_bounds(x, y) {
+                    self.check_move(p, x as u32, y as u32, true).ok()
+                } else {
+                    None
+                }
+            },
+            Action::Place(ref p) => self.place(p.x() as u32, p.y() as u32, p.team()).ok(),
+            Action::Upgrade(ref sacrifice, ref old, _) => match self.check_move(sacrifice, old.x() as u32, old.y() as u32, true) {
+                Ok(upgrade @ Action::Upgrade(..)) => Some(upgrade),
+                _ => None,
+            },
+            _ => self.check_action(&action).ok().map(|_| action.clone()),
+        };
+
+        resolved.unwrap_or_else(|| Action::Pass(action.team()))
+    }
+
+    /// asks `player` for the action of the team to move, showing it only what that team sees
+    pub fn ask(&self, player: &mut dyn Player) -> ::std::result::Result<Action, String> {
+        if self.fog.is_none() {
+            return player.choose(self);
+        }
+
+        let action = player.choose(&self.view(self.turn))?;
+        Ok(self.resolve(action))
+    }
+
+    /// starts asking `player` the way `ask` does, for players that answer later; under fog
+    /// the answer is for the view, and still has to be resolved
+    pub fn ask_later(&self, player: &mut dyn Player) -> Option<Receiver<::std::result::Result<Action, String>>> {
+        if self.fog.is_none() {
+            player.choose_later(self)
+        } else {
+            player.choose_later(&self.view(self.turn))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::rc::Rc;
+    use game::test::{two_teams, place, act};
+    use game::pieces::*;
+    use game::player::RandomPlayer;
+    use game::rules;
+
+    #[test]
+    fn teams_only_see_their_reach() {
+        let mut game = two_teams().with_fog(Vision::Reach);
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+
+        assert!(game.sees(0, 0, 0) && game.sees(0, 1, 0) && game.sees(0, 0, 1));
+        assert!(!game.sees(0, 1, 1) && !game.sees(0, 4, 4));
+        assert_eq!(game.view(0).board().iter().count(), 1);
+
+        game.fog = Some(Vision::Radius(4));
+        assert_eq!(game.view(0).board().iter().count(), 2);
+    }
+
+    #[test]
+    fn vision_follows_the_board() {
+        let mut game = two_teams().with_fog(Vision::Radius(1)).with_rules(Box::new(rules::Strict));
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+        assert!(!game.sees(0, 0, 2));
+
+        act(&mut game, (0, 0), (0, 1));
+        assert!(game.sees(0, 0, 2));
+        game.undo_last();
+        assert!(!game.sees(0, 0, 2));
+
+        // a view plays by the very same rules
+        assert!(Rc::ptr_eq(&game.view(0).rules, &game.rules));
+    }
+
+    #[test]
+    fn moving_into_the_fog() {
+        let mut game = two_teams().with_fog(Vision::Radius(0));
+        game.board.insert(Piece::from_parts(0, 0, 0, 1, MoveDirSet::from(vec![(0, 1), (1, 0)]))).unwrap();
+        game.board.insert(Piece::from_parts(0, 1, 1, 1, MoveDirSet::from(vec![(1, 0)]))).unwrap();
+        game.board.insert(Piece::from_parts(1, 0, 1, 3, MoveDirSet::from(vec![(1, 0)]))).unwrap();
+
+        let p = game.get_piece(0, 0).cloned().unwrap();
+        assert_eq!(game.view(0).board().iter().count(), 1);
+
+        // the view thinks both are plain moves
+        match game.resolve(Action::Move(p.clone(), 0, 1)) {
+            Action::Composite(_) => (),
+            other => panic!("expected a capture, got {:?}", other),
+        }
+        match game.resolve(Action::Move(p, 1, 0)) {
+            Action::Pass(0) => (),
+            other => panic!("expected a pass, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn upgrades_roll_on_the_real_board() {
+        let mut game = two_teams().with_fog(Vision::Radius(4)).with_seed(5);
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 3);
+
+        let p = game.get_piece(1, 0).cloned().unwrap();
+        let view = game.view(0);
+        assert_eq!(view.seed, game.seed);
+
+        // the view has no history, so only the real board knows what the upgrade rolls
+        let chosen = view.check_move(&p, 0, 0, true).unwrap();
+        let real = game.check_move(&p, 0, 0, true).unwrap();
+        assert_eq!(game.resolve(chosen).pieces(), real.pieces());
+    }
+
+    #[test]
+    fn fogged_bots_see_the_walls() {
+        let mut game = two_teams().with_fog(Vision::Reach);
+        game.board.insert(Piece::from_parts(0, 0, 0, 1, MoveDirSet::from(vec![(0, 1), (1, 0)]))).unwrap();
+        game.board.insert(Piece::from_parts(4, 4, 1, 1, MoveDirSet::from(vec![(-1, 0)]))).unwrap();
+        game.board.set_wall(1, 0, true).unwrap();
+        game.teams[0].pieces_left = 0;
+
+        let mut bot = RandomPlayer::new(1);
+        for _ in 0..20 {
+            let action = game.ask(&mut bot).unwrap();
+            assert_eq!(action.squares(), vec![(0, 0), (0, 1)]);
+        }
+    }
+}
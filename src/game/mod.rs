@@ -1,129 +1,377 @@
 use std::sync::atomic::AtomicPtr;
 use std::convert::From;
 use std::sync::Arc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::mem;
+use std::collections::BTreeSet;
 
 use piston_window::*;
+use rand::{Rng, thread_rng};
+use rand::prng::XorShiftRng;
 // use piston_window::character::CharacterCache;
 // use find_folder::Search;
 
 pub mod pieces;
 pub mod action;
+pub mod zone;
+pub mod clock;
+pub mod save;
+pub mod anim;
+pub mod error;
+pub mod board;
+pub mod bitboard;
+pub mod replay;
+pub mod export;
+pub mod player;
+pub mod engine;
+pub mod rules;
+pub mod fog;
+pub mod spectate;
+pub mod stats;
+pub mod puzzle;
+pub mod editor;
+pub mod cursor;
+pub mod theme;
+pub mod pips;
+pub mod events;
 
 use super::gui::{App, Data, AppGraphics, unwrap};
 use self::pieces::*;
 use self::action::*;
 use self::action::composite_vals::*;
+use self::zone::PlacementZone;
+use self::clock::*;
+use self::anim::Animator;
+use self::error::RuleError;
+use self::board::Board;
+use self::player::Player;
+use self::rules::{Rules, Standard};
+use self::fog::Vision;
+use self::spectate::Broadcast;
+use self::stats::Stats;
+use self::theme::{Theme, lighten};
+use self::pips::{Mark, BODY, level_marks};
+use self::events::{Event, Observer};
 
-const BLACK   : [f32; 4] = [0.0, 0.0, 0.0, 1.0];
-const WHITE   : [f32; 4] = [1.0, 1.0, 1.0, 1.0];
-const SELECTED: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+// height in pixels of the status bar above the board, one row for the clocks and one for hints
+const HUD_ROW: u32 = 26;
+const HUD_HEIGHT: u32 = 2 * HUD_ROW;
+const HUD_FONT_SIZE: u32 = 16;
 
-const TON: f32 = 1.0;
-const PON: f32 = 0.4;
-const OFF: f32 = 0.3;
-
-const SELECTED_MOVE_CAPTURE: [f32; 4] = [TON, PON, OFF, 1.0];
-const SELECTED_MOVE_UPGRADE: [f32; 4] = [OFF, TON, PON, 1.0];
-const SELECTED_MOVE_MOVE   : [f32; 4] = [PON, OFF, TON, 1.0];
+const SAVE_FILE: &str = "chess_like.save";
+const STATS_FILE: &str = "chess_like-stats.json";
 
+// the colours of new teams, unless the theme replaces them
 const TEAMS: [[f32; 4]; 3] = 
     [[180.0 / 255.0, 37.5  / 255.0, 180.0 / 255.0, 1.0]
     ,[180.0 / 255.0, 180.0 / 255.0, 37.5  / 255.0, 1.0]
     ,[37.5  / 255.0, 180.0 / 255.0, 180.0 / 255.0, 1.0]];
-const TEAMS_DIM: [[f32; 4]; 3] = 
-    [[240.0 / 255.0, 50.0   / 255.0, 240.0 / 255.0, 1.0]
-    ,[240.0 / 255.0, 240.0  / 255.0, 50.0  / 255.0, 1.0]
-    ,[50.0  / 255.0, 240.0  / 255.0, 240.0 / 255.0, 1.0]];
+
+fn blend(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0, (a[3] + b[3]) / 2.0]
+}
+
+/// What moving a piece onto a square does, known without rolling the outcome of an upgrade
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reach {
+    Move,
+    Upgrade,
+    Capture,
+}
 
 pub struct Game {
-    width: u32, height: u32,
     selected_x: u32, selected_y: u32,
-    pieces: Vec<Piece>, teams: Vec<Alliance>,
+    hover: Option<(u32, u32)>,
+    // the square the keyboard or a gamepad acts on, and the direction held down with the
+    // seconds until it repeats
+    cursor: Option<(u32, u32)>,
+    repeat: Option<(Button, f64)>,
+    board: Board, teams: Vec<Alliance>,
+    // the id the next piece to be placed gets
+    next_id: u32,
     turn: usize, action_stack: Vec<Action>,
+    redo_stack: Vec<Action>,
+    clock: Clock,
+    animator: Animator,
+    // pixels kept free below the board for other views, like the replay timeline
+    footer: u32,
     data: AtomicPtr<Data>,
     window: AtomicPtr<PistonWindow>,
+    glyphs: RefCell<Option<Glyphs>>,
+    // computer players that move for their team, and the answer one of them is still working
+    // out on another thread
+    bots: Vec<(usize, Box<dyn Player>)>,
+    thinking: Option<(usize, Receiver<Result<Action, String>>)>,
+    // shared with the views made for players under fog
+    rules: Rc<dyn Rules>,
+    // (team, other) for every alliance offered by team to other
+    offers: BTreeSet<(usize, usize)>,
+    fog: Option<Vision>,
+    // the squares `sees` last worked out and for which team, forgotten when the board changes
+    seen: RefCell<Option<(usize, Vision, Vec<bool>)>>,
+    // the team whose view is on screen, and whether the screen is hidden until the next
+    // player takes over
+    viewer: usize,
+    curtain: bool,
+    spectators: Option<Broadcast>,
+    // the report shown once the game is over, or why there is none
+    stats: Option<Result<Stats, String>>,
+    show_stats: bool,
+    theme: Theme,
+    // told about everything that happens, in the order they subscribed
+    observers: Vec<Box<dyn Observer>>,
+    // where upgrades get their new directions from
+    seed: u64,
 }
 
 // related functions
 impl Game {
     pub fn new(width: u32, height: u32) -> Self {
-        let team_1 = Alliance::new("Team 1", 20, TEAMS[0]);
-        let team_2 = Alliance::new("Team 2", 22, TEAMS[1]);
-        let team_3 = Alliance::new("Team 2", 24, TEAMS[2]);
+        let team_1 = Alliance::new("Team 1", 20, TEAMS[0])
+                        .with_zones(vec![PlacementZone::Rows(0, 2)]);
+        let team_2 = Alliance::new("Team 2", 22, TEAMS[1])
+                        .with_zones(vec![PlacementZone::Rows(height.saturating_sub(2), height)]);
+        let team_3 = Alliance::new("Team 3", 24, TEAMS[2])
+                        .with_zones(vec![PlacementZone::Region(0, 2, 2, height.saturating_sub(4)),
+                                         PlacementZone::AdjacentToOwn]);
 
         Game::from_vec(width, height, Vec::new(), vec![team_1, team_2, team_3])
+            .with_clock(Clock::new(TimeoutPolicy::Loss, vec![TimeControl::Fischer(600.0, 5.0); 3]))
     }
 
     pub fn from_vec(width: u32, height: u32, pieces: Vec<Piece>, teams: Vec<Alliance>) -> Self {
+        let clock = Clock::new(TimeoutPolicy::Loss, vec![TimeControl::Unlimited; teams.len()]);
+        let mut board = Board::new(width, height);
+        let mut next_id = pieces.iter().map(|p| p.id() + 1).max().unwrap_or(1);
+
+        for p in pieces {
+            let p = if p.id() == 0 {
+                next_id += 1;
+                p.with_id(next_id - 1)
+            } else {
+                p
+            };
+            if let Err(msg) = board.insert(p) {
+                println!("err = {}", msg);
+            }
+        }
+
         Self {
-            width, height, 
             selected_x: 100, selected_y: 100,
-            pieces, teams, turn: 0,
+            hover: None,
+            cursor: None,
+            repeat: None,
+            board, teams, next_id, turn: 0,
             action_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            clock,
+            animator: Animator::new(),
+            footer: 0,
             data: AtomicPtr::default(),
             window: AtomicPtr::default(),
+            glyphs: RefCell::new(None),
+            bots: Vec::new(),
+            thinking: None,
+            rules: Rc::new(Standard),
+            offers: BTreeSet::new(),
+            fog: None,
+            seen: RefCell::new(None),
+            viewer: 0,
+            curtain: false,
+            spectators: None,
+            stats: None,
+            show_stats: true,
+            observers: Vec::new(),
+            theme: Theme::classic(),
+            seed: thread_rng().gen(),
         }
     }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_rules(mut self, rules: Box<dyn Rules>) -> Self {
+        self.rules = Rc::from(rules);
+        self
+    }
+
+    /// rolls every upgrade from `seed`, so the same actions always make the same pieces
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// lets `player` take every turn of `team`
+    pub fn with_bot(mut self, team: usize, player: Box<dyn Player>) -> Self {
+        self.bots.push((team, player));
+        self
+    }
+
+    /// tells `observer` about every event of the game from now on
+    pub fn with_observer(mut self, observer: Box<dyn Observer>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// sends the game to everyone who connects to `broadcast`
+    pub fn with_spectators(mut self, broadcast: Broadcast) -> Self {
+        self.spectators = Some(broadcast);
+        self
+    }
 }
 
 // immutable functions
 impl Game {
-    fn get_piece(&self, x: u32, y: u32) -> Option<&Piece> {
-        let (x, y) = (x as i32, y as i32);
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
 
-        for p in &self.pieces {
-            if p.x() == x && p.y() == y {
-                return Some(p);
-            }
-        }
+    pub fn teams(&self) -> &[Alliance] {
+        &self.teams
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
 
-        None
+    pub fn get_piece(&self, x: u32, y: u32) -> Option<&Piece> {
+        self.board.get(x as i32, y as i32)
     }
     
+    // the size of a tile and the offsets that centre the board between the hud and the footer,
+    // worked out from the window size every time so that resizing needs no bookkeeping
     fn get_tile_size(&self) -> (f64, f64, f64) {
         let data = unwrap(&self.data);
-        let (s1, s2) = (data.screen_width as f64 / self.width as f64, data.screen_height as f64 / self.height as f64);
+        let (width, height) = (self.board.width() as f64, self.board.height() as f64);
+        let board_width = data.screen_width as f64;
+        let board_height = (self.footer_top() - HUD_HEIGHT as f64).max(0.0);
 
-        let s = {
-            if s1 < s2 {
-                s1
-            } else {
-                s2
-            }
-        };
+        // whole pixels keep the edges between tiles sharp
+        let s = (board_width / width).min(board_height / height).floor().max(1.0);
 
-        (s, (data.screen_width  - s as u32 * self.width ) as f64 / 2.0, 
-            (data.screen_height - s as u32 * self.height) as f64 / 2.0)
+        (s, (board_width - s * width) / 2.0, (board_height - s * height) / 2.0 + HUD_HEIGHT as f64)
+    }
+
+    /// where the footer starts, in window coordinates
+    fn footer_top(&self) -> f64 {
+        let data = unwrap(&self.data);
+
+        (data.screen_height as f64 - self.footer as f64).max(0.0)
     }
     
-    fn to_grid(&self, x: f64, y: f64) -> (u32, u32) {
+    // maps board coordinates, one unit per tile, onto the screen
+    fn board_transform(&self, c: Context) -> math::Matrix2d {
         let (s, dw, dh) = self.get_tile_size();
-        
-        let (x, y) = (x - dw, y - dh);
 
-        ((x / s) as u32, (y / s) as u32)
+        c.transform.trans(dw, dh).scale(s, s)
+    }
+
+    fn draw_text(&self, color: [f32; 4], text: &str, x: f64, y: f64, c: Context, g: &mut AppGraphics) {
+        if let Some(ref mut glyphs) = *self.glyphs.borrow_mut() {
+            // rasterize at the size of the screen's pixels, so text stays sharp on HiDPI screens
+            let scale = unwrap(&self.data).scale;
+            let transform = c.transform.trans(x, y).scale(1.0 / scale, 1.0 / scale);
+            let _ = graphics::text(color, (HUD_FONT_SIZE as f64 * scale).round() as u32, text, glyphs, transform, g);
+        }
+    }
+
+    // the tile under the cursor at (x, y), or `None` in the margins around the board
+    fn tile_at(&self, x: f64, y: f64) -> Option<(u32, u32)> {
+        let (s, dw, dh) = self.get_tile_size();
+        let (i, j) = (((x - dw) / s).floor(), ((y - dh) / s).floor());
+
+        if i < 0.0 || j < 0.0 || i >= self.board.width() as f64 || j >= self.board.height() as f64 {
+            None
+        } else {
+            Some((i as u32, j as u32))
+        }
     }
 
     fn tile_color(&self, i: u32, j: u32, piece: Option<&Piece>) -> [f32; 4] {
-        if i == self.selected_x && j == self.selected_y {
-            SELECTED
-        }
-        else if let Some(action) = self.can_move(piece, i, j, false) {
-            match action {
-                Action::Place(_) => SELECTED,
-                Action::Remove(_) => panic!("remove action leaked into draw loop"),
-                Action::Move(_, _, _) => SELECTED_MOVE_MOVE,
-                Action::Upgrade(_, _) => SELECTED_MOVE_UPGRADE,
-                Action::Composite(_) => SELECTED_MOVE_CAPTURE
-            }
+        let hovered = self.hover.filter(|&(x, y)| self.on_screen(x, y)).and_then(|(x, y)| self.get_piece(x, y));
+
+        if self.board.is_wall(i as i32, j as i32) {
+            self.theme.wall
+        }
+        else if i == self.selected_x && j == self.selected_y {
+            self.theme.selected
+        }
+        else if let Some(reach) = self.reach(piece, i, j) {
+            self.theme.reach_color(reach)
         }
-        else if (i + j) % 2 == 0 {
-            TEAMS_DIM[self.turn]
+        else if let (None, Some(reach)) = (piece, self.reach(hovered, i, j)) {
+            // only a preview, so mix it with the board underneath
+            blend(self.theme.reach_color(reach), self.checker_color(i, j))
+        }
+        else if piece.is_none() && self.can_place_at(i, j, self.turn) {
+            self.theme.place_zone
+        }
+        else {
+            self.checker_color(i, j)
+        }
+    }
+
+    fn checker_color(&self, i: u32, j: u32) -> [f32; 4] {
+        if (i + j).is_multiple_of(2) {
+            self.theme.light.unwrap_or_else(|| lighten(self.team_color(self.turn)))
         } else {
-            BLACK
+            self.theme.dark
         }
     }
 
+    /// the colour `team` is drawn in, which the theme may change from the team's own
+    pub fn team_color(&self, team: usize) -> [f32; 4] {
+        self.theme.teams.get(team).cloned().unwrap_or(self.teams[team].color)
+    }
+
+    // whether `team` could place a new piece at (x, y) right now
+    fn can_place_at(&self, x: u32, y: u32, team: usize) -> bool {
+        self.place(x, y, team).is_ok()
+    }
+
+    /// whether `team` and `other` have both offered an alliance to each other
+    pub fn allied(&self, team: usize, other: usize) -> bool {
+        self.offers.contains(&(team, other)) && self.offers.contains(&(other, team))
+    }
+
+    fn active_teams(&self) -> Vec<usize> {
+        (0..self.teams.len()).filter(|&t| !self.teams[t].eliminated).collect()
+    }
+
+    /// the game is over once every team left is allied with every other one
+    pub fn is_over(&self) -> bool {
+        let active = self.active_teams();
+
+        active.iter().all(|&a| active.iter().all(|&b| a == b || self.allied(a, b)))
+    }
+
+    /// the teams that share the victory, if the game is over
+    pub fn winners(&self) -> Vec<usize> {
+        if self.is_over() {
+            self.active_teams()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // whether (x, y) is shown to whoever is looking at the screen
+    fn on_screen(&self, x: u32, y: u32) -> bool {
+        self.fog.is_none() || self.is_over() || self.sees(self.viewer, x, y)
+    }
+
+    fn is_bot(&self, team: usize) -> bool {
+        self.bots.iter().any(|&(t, _)| t == team)
+    }
+
     fn print_team_pieces_left(&self) {
         for t in &self.teams {
             println!("team {}: {}", t.name, t.pieces_left);
@@ -131,203 +379,680 @@ impl Game {
         println!();
     }
 
-    fn can_move(&self, piece: Option<&Piece>, x: u32, y: u32, ignore_teams: bool) -> Option<Action> {
+    // draws `p` with its top left corner at (x, y), grown by `scale` around its centre
+    #[allow(clippy::too_many_arguments)]
+    fn draw_piece(&self, p: &Piece, x: f64, y: f64, scale: f64, alpha: f32, transform: math::Matrix2d, g: &mut AppGraphics) {
+        use graphics::*;
+
+        let transform = transform.trans(x + 0.5, y + 0.5).scale(scale, scale).trans(-0.5, -0.5);
+        let mut color = self.team_color(p.team());
+        color[3] *= alpha;
+        let mut text = self.theme.text;
+        text[3] *= alpha;
+
+        match self.theme.marker(p.team()).corners(BODY) {
+            Some(corners) => polygon(color, &corners, transform, g),
+            None => ellipse(color, BODY, transform, g),
+        }
+
+        for mark in level_marks(p.level()) {
+            match mark {
+                Mark::Pip(r) => ellipse(color, r, transform, g),
+                Mark::Bar(r) => rectangle(text, r, transform, g),
+            }
+        }
+    }
+
+    // draws the clocks of every team above the board
+    fn render_hud(&self, c: Context, g: &mut AppGraphics) {
+        use graphics::*;
+
+        let data = unwrap(&self.data);
+        let slot = data.screen_width as f64 / self.teams.len() as f64;
+        let baseline = (HUD_ROW + HUD_FONT_SIZE) as f64 / 2.0;
+
+        for (i, (team, timer)) in self.teams.iter().zip(self.clock.timers()).enumerate() {
+            let x = slot * i as f64;
+
+            if i == self.turn && !self.is_over() {
+                rectangle(self.team_color(i), [x, 0.0, slot, HUD_ROW as f64], c.transform, g);
+            }
+
+            let status = if team.eliminated {
+                String::from("out")
+            } else if self.winners().contains(&i) {
+                String::from("wins")
+            } else {
+                format_time(timer.remaining)
+            };
+            let allies: Vec<String> = (0..self.teams.len())
+                .filter(|&o| o != i && self.allied(i, o))
+                .map(|o| (o + 1).to_string())
+                .collect();
+            let label = if allies.is_empty() {
+                format!("{}  {}", team.name, status)
+            } else {
+                format!("{} & {}  {}", team.name, allies.join(" & "), status)
+            };
+            let color = if i == self.turn { self.theme.text } else { self.team_color(i) };
+
+            self.draw_text(color, &label, x + 8.0, baseline, c, g);
+        }
+
+        if let Some(hint) = self.describe_hover() {
+            self.draw_text(self.theme.text, &hint, 8.0, baseline + HUD_ROW as f64, c, g);
+        }
+    }
+
+    // the roll for merging `sacrifice` into `target` now, which depends only on the seed, the
+    // length of the history and the two pieces, however often it is asked for
+    fn upgrade_rng(&self, target: &Piece, sacrifice: &Piece) -> XorShiftRng {
+        let ply = self.action_stack.len() as u64;
+        let pieces = u64::from(target.id()) << 32 | u64::from(sacrifice.id());
+        player::seeded(self.seed ^ ply.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ pieces.rotate_left(17))
+    }
+
+    fn reach(&self, piece: Option<&Piece>, x: u32, y: u32) -> Option<Reach> {
+        piece.and_then(|p| self.check_reach(p, x, y, false).ok())
+    }
+
+    // the action `p` would take by moving to (x, y), or why it can't
+    pub fn check_move(&self, p: &Piece, x: u32, y: u32, ignore_teams: bool) -> Result<Action, RuleError> {
+        let (dx, dy) = (x as i32 - p.x(), y as i32 - p.y());
+
+        match (self.check_reach(p, x, y, ignore_teams)?, self.get_piece(x, y)) {
+            (Reach::Upgrade, Some(other)) => {
+                // roll the upgrade now, so that the action records exactly what it will do
+                let new = self.rules.upgrade(other, p, &mut self.upgrade_rng(other, p))?;
+                Ok(Action::Upgrade(p.clone(), other.clone(), new))
+            },
+            (Reach::Capture, Some(other)) => Ok(Action::Composite(Composite::Capture(
+                Arc::new(CaptureVal::from([
+                    Action::Remove(other.clone()),
+                    Action::Move(p.clone(), dx, dy)
+                ])
+            )))),
+            _ => Ok(Action::Move(p.clone(), dx, dy)),
+        }
+    }
+
+    // what moving `p` to (x, y) would do, or why it can't, checked without rolling anything
+    // so that drawing the board may ask for every square
+    pub fn check_reach(&self, p: &Piece, x: u32, y: u32, ignore_teams: bool) -> Result<Reach, RuleError> {
         let (ix, iy) = (x as i32, y as i32);
 
-        match piece {
-            None => None,
-            Some(p) => {
-                
-                if ignore_teams && p.team() != self.turn {
-                    None
-                } else if !p.can_move(MoveDir::new(ix - p.x(), iy - p.y())) {
-                    None
-                } else if let Some(other) = self.get_piece(x, y) {
-                    if other.team() == p.team() {
-                        Some(Action::Upgrade(p.clone(), other.clone()))
-                    } else if p.level() + 1 >= other.level() {
-                        Some(Action::Composite(Composite::Capture(
-                            Arc::new(CaptureVal::from([
-                                Action::Remove(other.clone()),
-                                Action::Move(p.clone(), ix - p.x(), iy - p.y())
-                            ])
-                        )) ))
-                    } else {
-                        None
-                    }
+        if !self.board.in_bounds(ix, iy) {
+            Err(RuleError::OutOfBounds(ix, iy))
+        } else if self.board.is_wall(ix, iy) {
+            Err(RuleError::Wall(ix, iy))
+        } else if ignore_teams && p.team() != self.turn {
+            Err(RuleError::NotYourTurn(p.team()))
+        } else if !p.can_move(MoveDir::new(ix - p.x(), iy - p.y())) {
+            Err(RuleError::IllegalDirection(ix - p.x(), iy - p.y()))
+        } else if let Some(other) = self.get_piece(x, y) {
+            let allied = self.allied(p.team(), other.team());
+
+            if other.team() == p.team() || (allied && self.rules.merge_with_allies()) {
+                let level = p.level() + other.level();
+                if level > self.rules.max_level() {
+                    Err(RuleError::LevelTooHigh(level))
                 } else {
-                    Some(Action::Move(p.clone(), ix - p.x(), iy - p.y()))
+                    Ok(Reach::Upgrade)
                 }
+            } else if allied {
+                Err(RuleError::CaptureAlly(other.team()))
+            } else if self.rules.can_capture(p, other) {
+                Ok(Reach::Capture)
+            } else {
+                Err(RuleError::CaptureNotAllowed(p.level(), other.level()))
             }
+        } else {
+            Ok(Reach::Move)
         }
     }
+
+    // checks that `action` fits the board as it is now
+    pub fn check_action(&self, action: &Action) -> Result<(), RuleError> {
+        // the piece must stand on the board exactly as the action names it
+        let exists = |p: &Piece| if self.board.get(p.x(), p.y()) == Some(p) {
+            Ok(())
+        } else if self.board.contains(p) {
+            Err(RuleError::MalformedAction("the piece is not as it stands on the board"))
+        } else {
+            Err(RuleError::NoPiece(p.x(), p.y()))
+        };
+        let is_empty = |x: i32, y: i32| {
+            if !self.board.in_bounds(x, y) {
+                Err(RuleError::OutOfBounds(x, y))
+            } else if self.board.is_wall(x, y) {
+                Err(RuleError::Wall(x, y))
+            } else if self.board.get(x, y).is_some() {
+                Err(RuleError::Occupied(x, y))
+            } else {
+                Ok(())
+            }
+        };
+        // what `p` does by moving to (x, y), checked as a click on the board would be
+        let reach = |p: &Piece, x: i32, y: i32| if self.board.in_bounds(x, y) {
+            self.check_reach(p, x as u32, y as u32, false)
+        } else {
+            Err(RuleError::OutOfBounds(x, y))
+        };
+
+        if action.team() >= self.teams.len() {
+            return Err(RuleError::MalformedAction("the action belongs to no team"));
+        }
+
+        match *action {
+            Action::Place(ref p) => {
+                is_empty(p.x(), p.y())?;
+                self.rules.check_place(self, p.x() as u32, p.y() as u32, p.team())?;
+                if p.id() < self.next_id {
+                    return Err(RuleError::MalformedAction("a placed piece needs an id no piece has had yet"));
+                }
+                Ok(())
+            },
+            Action::Remove(ref p) => exists(p),
+            Action::Move(ref p, dx, dy) => {
+                exists(p)?;
+                is_empty(p.x() + dx, p.y() + dy)?;
+                reach(p, p.x() + dx, p.y() + dy).map(|_| ())
+            },
+            Action::Upgrade(ref sacrifice, ref old, ref new) => {
+                exists(sacrifice)?;
+                exists(old)?;
+                if (sacrifice.x(), sacrifice.y()) == (old.x(), old.y()) || (new.x(), new.y()) != (old.x(), old.y()) {
+                    return Err(RuleError::MalformedAction("an upgrade must merge two pieces into the square of the second"));
+                }
+                if reach(sacrifice, old.x(), old.y())? != Reach::Upgrade {
+                    return Err(RuleError::MalformedAction("an upgrade must merge pieces of one team or of allies"));
+                }
+                self.rules.check_upgrade(old, sacrifice, new)
+            },
+            Action::Composite(Composite::Capture(ref c)) => {
+                match (&c.remove_action, &c.move_action) {
+                    (Action::Remove(victim), Action::Move(p, dx, dy)) => {
+                        exists(victim)?;
+                        exists(p)?;
+                        if (p.x() + *dx, p.y() + *dy) != (victim.x(), victim.y()) {
+                            return Err(RuleError::MalformedAction("a capture must move onto the captured piece"));
+                        }
+                        // the rules of the variant and alliances decide, as they do for a click
+                        match reach(p, victim.x(), victim.y())? {
+                            Reach::Capture => Ok(()),
+                            _ => Err(RuleError::MalformedAction("a capture must take a piece of a team that is not allied")),
+                        }
+                    },
+                    _ => Err(RuleError::MalformedAction("a capture must remove a piece and then move onto it"))
+                }
+            },
+            Action::Pass(_) | Action::Forfeit(_) => Ok(()),
+            Action::Ally(team, other) | Action::Break(team, other) => {
+                if team == other || other >= self.teams.len() {
+                    return Err(RuleError::MalformedAction("diplomacy needs two different teams"));
+                }
+
+                match (action, self.offers.contains(&(team, other))) {
+                    (Action::Ally(_, _), true) => Err(RuleError::AlreadyOffered(other)),
+                    (Action::Break(_, _), false) => Err(RuleError::NotOffered(other)),
+                    _ => Ok(()),
+                }
+            },
+        }
+    }
+
+    // a line for the move list
+    fn describe(&self, action: &Action) -> String {
+        let team = &self.teams[action.team()];
+
+        match *action {
+            Action::Place(ref p) => format!("{} places at ({}, {})", team, p.x(), p.y()),
+            Action::Remove(ref p) => format!("{} removes ({}, {})", team, p.x(), p.y()),
+            Action::Move(ref p, dx, dy) =>
+                format!("{} moves ({}, {}) to ({}, {})", team, p.x(), p.y(), p.x() + dx, p.y() + dy),
+            Action::Upgrade(_, ref old, ref new) =>
+                format!("{} upgrades ({}, {}) to level {}", team, old.x(), old.y(), new.level()),
+            Action::Composite(Composite::Capture(ref c)) => match c.remove_action {
+                Action::Remove(ref victim) =>
+                    format!("{} captures at ({}, {})", team, victim.x(), victim.y()),
+                _ => format!("{} captures", team),
+            },
+            Action::Pass(_) => format!("{} passes", team),
+            Action::Ally(_, other) => format!("{} offers an alliance to {}", team, self.teams[other]),
+            Action::Break(_, other) => format!("{} breaks with {}", team, self.teams[other]),
+            Action::Forfeit(_) => format!("{} is out", team),
+        }
+    }
+
+    // a description of what clicking on the hovered tile would do
+    fn describe_hover(&self) -> Option<String> {
+        let (x, y) = self.hover?;
+
+        if !self.on_screen(x, y) {
+            return Some(String::from("fog"));
+        }
+
+        if x == self.selected_x && y == self.selected_y {
+            return match self.get_piece(x, y) {
+                Some(p) => Some(format!("level {} piece of {}", p.level(), self.teams[p.team()])),
+                None => Some(match self.place(x, y, self.turn) {
+                    Ok(_) => String::from("click again to place a piece"),
+                    Err(msg) => format!("illegal: {}", msg),
+                }),
+            };
+        }
+
+        let selected = self.get_piece(self.selected_x, self.selected_y)?;
+        let other = self.get_piece(x, y);
+
+        Some(match (self.check_reach(selected, x, y, true), other) {
+            (Ok(Reach::Upgrade), Some(other)) => format!("upgrade to level {}", selected.level() + other.level()),
+            (Ok(Reach::Capture), Some(other)) =>
+                format!("capture the level {} piece of {}", other.level(), self.teams[other.team()]),
+            (Ok(_), _) => format!("move to ({}, {})", x, y),
+            (Err(msg), _) => format!("illegal: {}", msg),
+        })
+    }
+
+    /// every placement and move the team to move may take, or a pass if there is none
+    ///
+    /// diplomacy is left out, since it is always possible
+    pub fn legal_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+
+        for y in 0..self.board.height() {
+            for x in 0..self.board.width() {
+                if let Ok(action) = self.place(x, y, self.turn) {
+                    actions.push(action);
+                }
+            }
+        }
+
+        for p in self.board.iter().filter(|p| p.team() == self.turn) {
+            for m in p.poss_moves.moves() {
+                let (x, y) = (p.x() + m.dx(), p.y() + m.dy());
+                if self.board.in_bounds(x, y) {
+                    if let Ok(action) = self.check_move(p, x as u32, y as u32, true) {
+                        actions.push(action);
+                    }
+                }
+            }
+        }
+
+        if actions.is_empty() {
+            actions.push(Action::Pass(self.turn));
+        }
+
+        actions
+    }
 }
 
 // mutable functions
 impl Game {
     fn inc(&mut self) {
-        self.turn = (self.turn + 1) % self.teams.len();
+        self.turn = self.rules.next_turn(&self.teams, self.turn);
+    }
+
+    fn time_out(&mut self) {
+        let team = self.turn;
+        println!("{} ran out of time", self.teams[team]);
+
+        match self.clock.policy {
+            TimeoutPolicy::Loss => self.forfeit(team),
+            TimeoutPolicy::Pass => {
+                if let Err(msg) = self.do_action(Action::Pass(team)) {
+                    println!("err = {}", msg);
+                }
+            },
+        }
+
+        self.selected_x = 100;
+        self.selected_y = 100;
     }
 
-    fn dec(&mut self) {
-        let len = self.teams.len();
-        self.turn = (self.turn + len - 1) % len;
+    // takes `team` out of the game through the history, so that undo and replays bring it back
+    fn forfeit(&mut self, team: usize) {
+        if let Err(msg) = self.do_action(Action::Forfeit(team)) {
+            println!("err = {}", msg);
+        }
     }
 
-    fn place(&mut self, x: u32, y: u32, team: usize) -> Result<Action, String> {
-        let is_empty = self.get_piece(x, y).is_none();
-        
-        if self.teams[team].pieces_left == 0 {
-            Err(String::from("Cannot place anymore pieces"))
+    fn emit(&mut self, event: Event) {
+        let mut observers = mem::take(&mut self.observers);
+
+        for observer in &mut observers {
+            observer.notify(self, &event);
         }
-        else if is_empty {
-            let piece = Piece::new(x as u16, y as u16, self.turn);
-            Ok(Action::Place(piece))
-        } else {
-            Err(String::from("Cannot place ontop of another piece"))
+        self.observers = observers;
+
+        if let Some(mut spectators) = self.spectators.take() {
+            spectators.notify(self, &event);
+            self.spectators = Some(spectators);
         }
     }
-    
-    fn remove_piece_at(&mut self, x: i32, y: i32) -> Result<(), String> {
-        let pos = self.pieces.iter().position(|p| p.x() == x && p.y() == y);
-        
-        match pos {
-            Some(index) => {
-                self.pieces.remove(index);
-                Ok(())
+
+    // lets the bot of the team to move, if there is one, take its turn, or checks whether the
+    // bot that is thinking has answered
+    fn play_bot(&mut self) {
+        let answer = match self.thinking.take() {
+            Some((team, pending)) => match pending.try_recv() {
+                Ok(answer) => Some((team, answer.map(|action| if self.fog.is_some() { self.resolve(action) } else { action }))),
+                Err(TryRecvError::Empty) => {
+                    self.thinking = Some((team, pending));
+                    None
+                },
+                Err(TryRecvError::Disconnected) => Some((team, Err(String::from("it stopped answering")))),
             },
-            None =>
-                Err(format!("No piece at ({}, {})", x, y))
+            None => {
+                let mut bots = mem::take(&mut self.bots);
+                let turn = self.turn;
+                let answer = match bots.iter_mut().find(|&&mut (team, _)| team == turn) {
+                    Some(&mut (team, ref mut bot)) => match self.ask_later(&mut **bot) {
+                        Some(pending) => {
+                            self.thinking = Some((team, pending));
+                            None
+                        },
+                        None => Some((team, self.ask(&mut **bot))),
+                    },
+                    None => None,
+                };
+                self.bots = bots;
+                answer
+            },
+        };
+
+        // an answer for a team that is no longer to move is dropped
+        if let Some((team, answer)) = answer.filter(|&(team, _)| team == self.turn) {
+            if let Err(msg) = answer.and_then(|action| self.do_action(action).map_err(|e| e.to_string())) {
+                let name = self.bots.iter().find(|&&(t, _)| t == team).map_or("the bot", |(_, bot)| bot.name()).to_string();
+                println!("err = {} forfeits: {}", name, msg);
+                self.forfeit(team);
+            }
         }
     }
 
-    fn place_piece(&mut self, p: &Piece) -> Result<(), String> {
-        let is_empty = self.get_piece(p.x() as u32, p.y() as u32).is_none();
+    fn lift_curtain(&mut self) {
+        self.curtain = false;
+        self.viewer = self.turn;
+        self.selected_x = 100;
+        self.selected_y = 100;
+    }
 
-        if is_empty {
-            self.pieces.push(p.clone());
-            Ok(())
+    // offers an alliance to `other` for the team to move, or withdraws the offer
+    fn diplomacy(&mut self, other: usize) {
+        let team = self.turn;
+        if other >= self.teams.len() || self.is_bot(team) {
+            return;
+        }
+
+        if self.curtain {
+            return;
+        }
+
+        let action = if self.offers.contains(&(team, other)) {
+            Action::Break(team, other)
+        } else {
+            Action::Ally(team, other)
+        };
+
+        match self.do_action(action) {
+            Ok(()) if self.allied(team, other) => println!("{} and {} are allied", self.teams[team], self.teams[other]),
+            Ok(()) if self.offers.contains(&(team, other)) =>
+                println!("{} offers an alliance to {}", self.teams[team], self.teams[other]),
+            Ok(()) => println!("{} breaks with {}", self.teams[team], self.teams[other]),
+            Err(msg) => println!("err = {}", msg),
+        }
+    }
+
+    // replaces the game state with `game`, keeping the window and gui state
+    fn restore(&mut self, mut game: Game) {
+        mem::swap(&mut game.data, &mut self.data);
+        mem::swap(&mut game.window, &mut self.window);
+        mem::swap(&mut game.glyphs, &mut self.glyphs);
+        game.footer = self.footer;
+        game.animator.enabled = self.animator.enabled;
+        mem::swap(&mut game.bots, &mut self.bots);
+        mem::swap(&mut game.spectators, &mut self.spectators);
+        mem::swap(&mut game.theme, &mut self.theme);
+        mem::swap(&mut game.observers, &mut self.observers);
+
+        *self = game;
+        self.emit(Event::Loaded);
+    }
+
+    pub fn place(&self, x: u32, y: u32, team: usize) -> Result<Action, RuleError> {
+        if x >= self.board.width() || y >= self.board.height() {
+            Err(RuleError::OutOfBounds(x as i32, y as i32))
+        } else if self.board.is_wall(x as i32, y as i32) {
+            Err(RuleError::Wall(x as i32, y as i32))
+        } else if self.get_piece(x, y).is_some() {
+            Err(RuleError::Occupied(x as i32, y as i32))
         } else {
-            Err(String::from("Cannot place ontop of another piece"))
+            self.rules.check_place(self, x, y, team)?;
+            Ok(Action::Place(Piece::new(x as u16, y as u16, team).with_id(self.next_id)))
         }
     }
 
-    fn remove_piece(&mut self, piece: &Piece) -> Result<(), String> {
-        let pos = self.pieces.iter().position(|p| p == piece);
+    // an id no piece of this game has had yet
+    fn new_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
 
-        match pos {
-            Some(index) => {
-                self.pieces.remove(index);
-                Ok(())
-            },
-            None =>
-                Err(format!("Piece does not exists in this game: {}", piece))
+    // drops the vision `sees` kept, after anything on the board changed
+    fn forget_vision(&self) {
+        *self.seen.borrow_mut() = None;
+    }
+
+    // the id after every one on the board or in the history
+    fn first_free_id(&self) -> u32 {
+        self.board.iter().chain(self.action_stack.iter().flat_map(|a| a.pieces()))
+            .map(|p| p.id() + 1).max().unwrap_or(1)
+    }
+
+    fn place_piece(&mut self, p: &Piece) -> Result<(), RuleError> {
+        self.board.insert(p.clone())
+    }
+
+    fn remove_piece(&mut self, piece: &Piece) -> Result<(), RuleError> {
+        self.board.remove(piece).map(|_| ())
+    }
+
+    // applies `action` and records it in the history, if it is the acting team's turn and
+    // the action fits the board, otherwise nothing changes
+    pub fn do_action(&mut self, action: Action) -> Result<(), RuleError> {
+        if self.is_over() {
+            return Err(RuleError::GameOver);
+        }
+        if action.team() != self.turn {
+            return Err(RuleError::NotYourTurn(action.team()));
         }
+        self.check_action(&action)?;
+
+        if action.ends_turn() {
+            self.clock.end_move(self.turn);
+        }
+        self.redo_stack.clear();
+        self.commit(action.clone());
+        self.animator.start(&action);
+        Ok(())
     }
 
-    fn do_action(&mut self, action: Action) {
-        if let Action::Place(p) = action.clone() {
+    // applies an action that has already been checked
+    fn commit(&mut self, action: Action) {
+        if let Action::Place(ref p) = action {
             self.teams[p.team()].pieces_left -= 1;
-            self.print_team_pieces_left();
+            self.next_id = self.next_id.max(p.id() + 1);
+        }
+        if action.ends_turn() {
+            self.inc();
         }
-        self.inc();
         self.action_stack.push(action.clone());
-        self.apply_action(action);
+        if let Err(msg) = self.apply_action(action.clone()) {
+            println!("err = {}", msg);
+        }
+        self.forget_vision();
+
+        for event in Event::of(&action) {
+            self.emit(event);
+        }
+        if action.ends_turn() {
+            self.emit(Event::Turn(self.turn));
+        }
+        if self.is_over() {
+            let winners = self.winners();
+            self.emit(Event::GameOver(winners));
+        }
     }
 
-    fn apply_action(&mut self, action: Action) {
+    fn apply_action(&mut self, action: Action) -> Result<(), RuleError> {
         match action {
-            Action::Place(p) => {
-                if let Err(msg) = self.place_piece(&p) {
-                    println!("err = {}", msg);
-                }
-            },
-            Action::Remove(p) => {
-                if let Err(msg) = self.remove_piece(&p) {
-                    println!("err = {}", msg);
-                }
-            },
+            Action::Place(p) => self.place_piece(&p),
+            Action::Remove(p) => self.remove_piece(&p),
             Action::Move(old, dx, dy) => {
                 let dir = MoveDir::new(dx, dy);
                 let mut new = old.clone();
                 new.apply(Move::from(dir));
 
-                self.apply_action(Action::Remove(old));
-                self.apply_action(Action::Place(new));
+                self.apply_action(Action::Remove(old))?;
+                self.apply_action(Action::Place(new))
             },
-            Action::Upgrade(sacrifice, old) => {
-                let mut new = old.clone();
-                if let Err(msg) = new.upgrade(&sacrifice) {
-                    println!("err = {}", msg);
-                    self.dec();
-                } else {
-                    self.apply_action(Action::Remove(old));
-                    self.apply_action(Action::Remove(sacrifice));
-                    self.apply_action(Action::Place(new));
-                }
+            Action::Upgrade(sacrifice, old, new) => {
+                self.apply_action(Action::Remove(old))?;
+                self.apply_action(Action::Remove(sacrifice))?;
+                self.apply_action(Action::Place(new))
             },
             Action::Composite(c) => {
                 match c {
                     Composite::Capture(c) => {
-                        self.apply_action(c.remove_action.clone());
-                        self.apply_action(c.move_action.clone());
+                        self.apply_action(c.remove_action.clone())?;
+                        self.apply_action(c.move_action.clone())
                     }
                 }
+            },
+            Action::Pass(_) => Ok(()),
+            Action::Ally(team, other) => {
+                self.offers.insert((team, other));
+                Ok(())
+            },
+            Action::Break(team, other) => {
+                self.offers.remove(&(team, other));
+                Ok(())
+            },
+            Action::Forfeit(team) => {
+                self.teams[team].eliminated = true;
+                Ok(())
+            },
+        }
+    }
+
+    pub fn undo_last(&mut self) {
+        self.animator.clear();
+        // whatever a bot is thinking about no longer fits the board
+        self.thinking = None;
+
+        if let Some(action) = self.action_stack.pop() {
+            if let Action::Place(ref p) = action {
+                self.teams[p.team()].pieces_left += 1;
+                self.print_team_pieces_left();
             }
+            // the turn order is up to the rules, so go back to whoever acted
+            self.turn = action.team();
+            if let Err(msg) = self.undo_action(action.clone()) {
+                println!("err = {}", msg);
+            }
+            self.forget_vision();
+            // the id of a piece taken back is free again, for redoing it or placing anew
+            if let Action::Place(_) = action {
+                self.next_id = self.first_free_id();
+            }
+            self.redo_stack.push(action.clone());
+            self.emit(Event::Undone(action));
+            self.emit(Event::Turn(self.turn));
         }
     }
 
-    fn undo_last(&mut self) {
-        match self.action_stack.pop() {
+    pub fn redo_last(&mut self) -> Result<(), RuleError> {
+        self.animator.clear();
+
+        match self.redo_stack.pop() {
             Some(action) => {
-                if let Action::Place(p) = action.clone() {
-                    self.teams[p.team()].pieces_left += 1;
-                            self.print_team_pieces_left();
+                if let Err(msg) = self.check_action(&action) {
+                    self.redo_stack.push(action);
+                    return Err(msg);
                 }
-                self.dec();
-                self.undo_action(action)
+                self.commit(action);
+                Ok(())
             },
-            None => (),
+            None => Err(RuleError::NothingToRedo),
         }
     }
 
-    fn undo_action(&mut self, action: Action) {
+    fn undo_action(&mut self, action: Action) -> Result<(), RuleError> {
         match action {
-            Action::Place(p) => {
-                if let Err(msg) = self.remove_piece(&p) {
-                    println!("err = {}", msg);
-                }
-            },
-            Action::Remove(p) => {
-                if let Err(msg) = self.place_piece(&p) {
-                    println!("err = {}", msg);
-                }
-            },
+            Action::Place(p) => self.remove_piece(&p),
+            Action::Remove(p) => self.place_piece(&p),
             Action::Move(old, dx, dy) => {
                 let dir = MoveDir::new(dx, dy);
                 let mut new = old.clone();
                 new.apply(Move::from(dir));
 
-                self.undo_action(Action::Remove(old));
-                self.undo_action(Action::Place(new));
+                self.undo_action(Action::Place(new))?;
+                self.undo_action(Action::Remove(old))
             },
-            Action::Upgrade(sacrifice, old) => {
-                if let Err(msg) = self.remove_piece_at(old.x(), old.y()) {
-                    println!("err = {}", msg);
-                }
-
-                self.undo_action(Action::Remove(old));
-                self.undo_action(Action::Remove(sacrifice));
+            Action::Upgrade(sacrifice, old, new) => {
+                self.undo_action(Action::Place(new))?;
+                self.undo_action(Action::Remove(sacrifice))?;
+                self.undo_action(Action::Remove(old))
             },
             Action::Composite(c) => {
                 match c {
                     Composite::Capture(c) => {
-                        self.undo_action(c.move_action.clone());
-                        self.undo_action(c.remove_action.clone());
+                        self.undo_action(c.move_action.clone())?;
+                        self.undo_action(c.remove_action.clone())
                     }
                 }
+            },
+            Action::Pass(_) => Ok(()),
+            Action::Ally(team, other) => {
+                self.offers.remove(&(team, other));
+                Ok(())
+            },
+            Action::Break(team, other) => {
+                self.offers.insert((team, other));
+                Ok(())
+            },
+            Action::Forfeit(team) => {
+                self.teams[team].eliminated = false;
+                Ok(())
+            },
+        }
+    }
+
+    /// undoes every action in the history and then redoes all of them, checking that
+    /// each step applies cleanly and that the board ends up exactly as it started
+    pub fn verify_history(&mut self) -> Result<(), String> {
+        let (board, teams, turn) = (self.board.clone(), self.teams.clone(), self.turn);
+        let redo_stack = mem::take(&mut self.redo_stack);
+        let count = self.action_stack.len();
+
+        for _ in 0..count {
+            self.undo_last();
+        }
+        let mut result = Ok(());
+        for i in 0..count {
+            if let Err(msg) = self.redo_last() {
+                result = Err(format!("action {} could not be redone: {}", i, msg));
+                break;
             }
         }
+        self.redo_stack = redo_stack;
+
+        result?;
+        if self.turn != turn || self.teams != teams || self.board != board {
+            return Err(String::from("the board is different after replaying the history"));
+        }
+        Ok(())
     }
 }
 
@@ -338,50 +1063,61 @@ impl App for Game {
 
     fn set_window(&mut self, window: AtomicPtr<PistonWindow>) {
         self.window = window;
+
+        let factory = unwrap(&self.window).factory.clone();
+        let font = include_bytes!("../res/FiraSans-Regular.ttf");
+        match Glyphs::from_bytes(font, factory, TextureSettings::new()) {
+            Ok(glyphs) => *self.glyphs.borrow_mut() = Some(glyphs),
+            Err(_) => println!("err = could not load the hud font"),
+        }
     }
 
     fn render(&self, c: Context, g: &mut AppGraphics) {
         use graphics::*;
 
-        let (s, dw, dh) = self.get_tile_size();
+        clear(self.theme.background, g);
+
+        if self.curtain {
+            let prompt = format!("pass the screen to {}, then click or press space", self.teams[self.turn]);
+            self.draw_text(self.theme.text, &prompt, 16.0, HUD_ROW as f64, c, g);
+            return;
+        }
 
-        clear(WHITE, g);
-        let transform = c.transform.trans(dw, dh).scale(s, s); //.trans((data.screen_width as f64 - sz), (data.screen_height as f64 - sz) / 2.0);
-        let piece = self.get_piece(self.selected_x, self.selected_y);
+        let transform = self.board_transform(c);
+        let piece = self.get_piece(self.selected_x, self.selected_y)
+            .filter(|p| self.on_screen(p.x() as u32, p.y() as u32));
 
-        for i in 0..self.width {
-            for j in 0..self.height {
-                let c = self.tile_color(i, j, piece);
+        for i in 0..self.board.width() {
+            for j in 0..self.board.height() {
+                let c = if self.on_screen(i, j) { self.tile_color(i, j, piece) } else { self.theme.fog };
 
                 let sq = rectangle::square(i as f64, j as f64, 1.0);
                 rectangle(c, sq, transform, g);
             }
         }
 
-        for p in &self.pieces {
-            let sq = rectangle::square(p.x() as f64 + 0.3, p.y() as f64 + 0.3, 0.4);
-            ellipse(self.teams[p.team()].color, sq, transform, g);
-            if p.level() > 1 {
-                let sq = rectangle::square(p.x() as f64 + 0.1, p.y() as f64 + 0.1, 0.2);
-                ellipse(self.teams[p.team()].color, sq, transform, g);
-            }
-            if p.level() > 2 {
-                let sq = rectangle::square(p.x() as f64 + 0.7, p.y() as f64 + 0.1, 0.2);
-                ellipse(self.teams[p.team()].color, sq, transform, g);
-            }
-            if p.level() > 3 {
-                let sq = rectangle::square(p.x() as f64 + 0.7, p.y() as f64 + 0.7, 0.2);
-                ellipse(self.teams[p.team()].color, sq, transform, g);
-            }
-            if p.level() > 4 {
-                let sq = rectangle::square(p.x() as f64 + 0.1, p.y() as f64 + 0.7, 0.2);
-                ellipse(self.teams[p.team()].color, sq, transform, g);
-            }
-            if p.level() > 5 {
-                let sq = rectangle::square(p.x() as f64 + 0.4, p.y() as f64 + 0.4, 0.2);
-                ellipse(self.tile_color(p.x() as u32, p.y() as u32, None), sq, transform, g);
+        self.render_hud(c, g);
+
+        for p in self.board.iter().filter(|p| self.on_screen(p.x() as u32, p.y() as u32)) {
+            if !self.animator.is_hidden(p.id()) {
+                self.draw_piece(p, p.x() as f64, p.y() as f64, 1.0, 1.0, transform, g);
             }
         }
+
+        if let Some(stats) = self.stats.as_ref().filter(|_| self.show_stats) {
+            self.render_stats(stats, c, g);
+            return;
+        }
+
+        let shown = |x: f64, y: f64| x >= 0.0 && y >= 0.0 && self.on_screen(x.round() as u32, y.round() as u32);
+        for frame in self.animator.frames().into_iter().filter(|f| shown(f.x, f.y)) {
+            self.draw_piece(frame.piece, frame.x, frame.y, frame.scale, frame.alpha, transform, g);
+        }
+
+        if let Some((x, y)) = self.cursor.filter(|&(x, y)| self.board.in_bounds(x as i32, y as i32)) {
+            let outline = [x as f64 + 0.05, y as f64 + 0.05, 0.9, 0.9];
+            Rectangle::new_border(self.theme.text, 0.05).draw(outline, &c.draw_state, transform, g);
+        }
         
         /*
         let font = Search::ParentsThenKids(3, 3)
@@ -400,22 +1136,138 @@ impl App for Game {
         */
     }
     
-    fn update(&mut self, _args: &UpdateArgs) {
+    fn update(&mut self, args: &UpdateArgs) {
         // println!("ups = {}", 1.0 / args.dt);
         // println!("{:?}", self.action_stack);
+        self.animator.update(args.dt);
+        self.wait_repeat(args.dt);
+
+        if !self.is_over() && self.clock.tick(self.turn, args.dt) {
+            self.time_out();
+        }
+        if !self.is_over() && !self.animator.is_busy() {
+            self.play_bot();
+        }
+
+        if !self.is_over() {
+            self.stats = None;
+        } else if self.stats.is_none() {
+            let stats = Stats::collect(self);
+            match stats {
+                Ok(ref stats) => {
+                    for line in stats.summary() {
+                        println!("{}", line);
+                    }
+                    match stats.write(STATS_FILE) {
+                        Ok(()) => println!("statistics written to {}", STATS_FILE),
+                        Err(msg) => println!("err = {}", msg),
+                    }
+                },
+                Err(ref msg) => println!("err = {}", msg),
+            }
+            self.stats = Some(stats);
+        }
+
+        if let Some(mut spectators) = self.spectators.take() {
+            spectators.update(self, args.dt);
+            self.spectators = Some(spectators);
+        }
+
+        // hide the board until the next player at the screen is ready
+        if self.fog.is_some() && !self.is_over() && !self.is_bot(self.turn) && self.turn != self.viewer {
+            self.curtain = true;
+        }
     }
 
     fn handle_key(&mut self, key: Key) {
         match key {
-            Key::Z => {
-                self.undo_last();
-            }
-            _ => (),
+            Key::Z if !self.animator.is_busy() => self.undo_last(),
+            Key::Y if !self.animator.is_busy() => {
+                if let Err(msg) = self.redo_last() {
+                    println!("err = {}", msg);
+                }
+            },
+            Key::V if !self.animator.is_busy() => {
+                match self.verify_history() {
+                    Ok(()) => println!("history verified, {} actions", self.action_stack.len()),
+                    Err(msg) => println!("err = {}", msg),
+                }
+            },
+            Key::T => self.show_stats = !self.show_stats,
+            Key::N => {
+                self.animator.enabled = !self.animator.enabled;
+                self.animator.clear();
+                println!("animations {}", if self.animator.enabled { "on" } else { "off" });
+            },
+            Key::F5 => {
+                match save::save_to_file(self, SAVE_FILE) {
+                    Ok(()) => println!("saved to {}", SAVE_FILE),
+                    Err(msg) => println!("err = {}", msg),
+                }
+            },
+            Key::F9 => {
+                match save::load_from_file(SAVE_FILE) {
+                    Ok(game) => self.restore(game),
+                    Err(msg) => println!("err = {}", msg),
+                }
+            },
+            // the number of a team offers it an alliance, or breaks the one already offered
+            Key::D1 | Key::D2 | Key::D3 | Key::D4 | Key::D5 | Key::D6 | Key::D7 | Key::D8 | Key::D9
+                if !self.animator.is_busy() => self.diplomacy(key as usize - Key::D1 as usize),
+            _ => self.press(Button::Keyboard(key)),
         }
     }
+
+    fn handle_controller(&mut self, controller_button: ControllerButton) {
+        self.press(Button::Controller(controller_button));
+    }
+
+    fn handle_key_held(&mut self, key: Key) {
+        self.hold(Button::Keyboard(key));
+    }
+
+    fn handle_controller_held(&mut self, controller_button: ControllerButton) {
+        self.hold(Button::Controller(controller_button));
+    }
     
+    fn mouse_moved(&mut self, args: &Motion) {
+        if let Motion::MouseCursor(x, y) = *args {
+            self.hover = self.tile_at(x, y);
+        }
+    }
+
+    fn handle_cursor(&mut self, cursor: bool) {
+        if !cursor {
+            self.hover = None;
+        }
+    }
+
+    // the tiles are laid out again from the new window size, so the mouse lands on another tile
+    fn handle_resize(&mut self, _width: u32, _height: u32) {
+        let data = unwrap(&self.data);
+
+        self.hover = self.tile_at(data.mouse_x, data.mouse_y).filter(|_| data.is_cursor_on);
+    }
+
     fn handle_mouse(&mut self, mouse_button: MouseButton, mouse_x: f64, mouse_y: f64) {
-        let (x, y) = self.to_grid(mouse_x, mouse_y);
+        if self.curtain {
+            self.lift_curtain();
+            return;
+        }
+
+        if let Some((x, y)) = self.tile_at(mouse_x, mouse_y) {
+            self.click(mouse_button, x, y);
+        }
+    }
+}
+
+impl Game {
+    // selects, places or moves as a click on the tile (x, y) does
+    fn click(&mut self, mouse_button: MouseButton, x: u32, y: u32) {
+        if self.is_over() || self.animator.is_busy() || self.is_bot(self.turn) {
+            return;
+        }
+
         let (sx, sy) = (self.selected_x, self.selected_y);
         let mut deselect = false;
 
@@ -424,18 +1276,16 @@ impl App for Game {
                 if sx == x && sy == y {
                     let turn = self.turn;
                     let place_action = self.place(x, y, turn);
-                    match place_action {
-                        Ok(action) => {
-                            self.do_action(action);
-                        },
+                    match place_action.and_then(|action| self.do_action(action)) {
+                        Ok(()) => self.print_team_pieces_left(),
                         Err(msg) => {
                             println!("msg = {}", msg)
                         }
                     }
-                } else {
-                    if let Some(action) = self.can_move(self.get_piece(sx, sy), x, y, true) {
-                        self.do_action(action);
-                        deselect = true;
+                } else if let Some(action) = self.get_piece(sx, sy).and_then(|p| self.check_move(p, x, y, true).ok()) {
+                    match self.do_action(action) {
+                        Ok(()) => deselect = true,
+                        Err(msg) => println!("msg = {}", msg),
                     }
                 }
 
@@ -453,4 +1303,262 @@ impl App for Game {
             }
         }; // end match
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    pub fn two_teams() -> Game {
+        let teams = vec![Alliance::new("A", 5, TEAMS[0]), Alliance::new("B", 5, TEAMS[1])];
+        Game::from_vec(5, 5, Vec::new(), teams)
+    }
+
+    pub fn act(game: &mut Game, from: (u32, u32), to: (u32, u32)) {
+        let p = game.get_piece(from.0, from.1).cloned().unwrap();
+        let action = game.check_move(&p, to.0, to.1, true).unwrap();
+        game.do_action(action).unwrap();
+    }
+
+    pub fn place(game: &mut Game, x: u32, y: u32) {
+        let action = game.place(x, y, game.turn).unwrap();
+        game.do_action(action).unwrap();
+    }
+
+    #[test]
+    fn clicks_around_the_board_miss() {
+        let mut data = Data {
+            is_cursor_on: true, is_window_focus: true,
+            screen_width: 600, screen_height: 400, scale: 2.0, fullscreen: false,
+            mouse_x: 0.0, mouse_y: 0.0, button_held: Vec::new(),
+        };
+        let mut game = two_teams();
+        game.set_data(AtomicPtr::new(&mut data));
+
+        // 69 pixel tiles, centred with 127.5 pixels on either side
+        assert_eq!(game.get_tile_size(), (69.0, 127.5, HUD_HEIGHT as f64 + 1.5));
+        assert_eq!(game.tile_at(10.0, 100.0), None);
+        assert_eq!(game.tile_at(590.0, 100.0), None);
+        assert_eq!(game.tile_at(300.0, 20.0), None);
+        assert_eq!(game.tile_at(127.5 + 69.0 * 2.0 + 1.0, HUD_HEIGHT as f64 + 69.0 * 4.5), Some((2, 4)));
+
+        data.screen_height = 10;
+        assert_eq!(game.get_tile_size().0, 1.0);
+    }
+
+    #[test]
+    fn resizing_moves_the_hover() {
+        let mut data = Data {
+            is_cursor_on: true, is_window_focus: true,
+            screen_width: 600, screen_height: 400, scale: 1.0, fullscreen: false,
+            mouse_x: 300.0, mouse_y: HUD_HEIGHT as f64 + 10.0, button_held: Vec::new(),
+        };
+        let mut game = two_teams();
+        game.set_data(AtomicPtr::new(&mut data));
+        game.handle_resize(600, 400);
+        assert_eq!(game.hover, Some((2, 0)));
+
+        // the board stays centred as the window widens, which moves it out from under the mouse
+        data.screen_width = 1000;
+        game.handle_resize(1000, 400);
+        assert_eq!(game.hover, None);
+    }
+
+    #[test]
+    fn undo_redo_is_exact() {
+        let mut game = two_teams();
+
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 2);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 4);
+        act(&mut game, (1, 0), (0, 0));
+        act(&mut game, (0, 2), (0, 1));
+        act(&mut game, (0, 0), (0, 1));
+
+        let board = game.board.clone();
+        let upgraded = game.get_piece(0, 1).cloned().unwrap();
+        assert_eq!(upgraded.level(), 2);
+
+        for _ in 0..7 {
+            game.undo_last();
+        }
+        assert_eq!(game.board.iter().next(), None);
+        assert_eq!(game.teams[0].pieces_left, 5);
+        assert_eq!(game.turn, 0);
+
+        for _ in 0..7 {
+            game.redo_last().unwrap();
+        }
+        assert_eq!(game.get_piece(0, 1), Some(&upgraded));
+        assert_eq!(game.board, board);
+        assert!(game.verify_history().is_ok());
+    }
+
+    #[test]
+    fn failed_actions_stay_out_of_history() {
+        let mut game = two_teams();
+
+        place(&mut game, 0, 0);
+        let taken = Action::Place(Piece::new(0, 0, 1));
+        assert_eq!(game.do_action(taken), Err(RuleError::Occupied(0, 0)));
+
+        let out_of_turn = Action::Place(Piece::new(1, 1, 0));
+        assert_eq!(game.do_action(out_of_turn), Err(RuleError::NotYourTurn(0)));
+        assert_eq!(game.place(7, 1, 1).err(), Some(RuleError::OutOfBounds(7, 1)));
+
+        // actions built by hand get the same checks as clicks on the board
+        place(&mut game, 4, 4);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 3);
+        let p = game.get_piece(0, 0).cloned().unwrap();
+        assert_eq!(game.do_action(Action::Move(p.clone(), 3, 3)), Err(RuleError::IllegalDirection(3, 3)));
+        match game.check_move(&p, 1, 0, true).unwrap() {
+            Action::Upgrade(sacrifice, old, new) => {
+                let forged = Piece::from_parts(1, 0, 0, 5, new.poss_moves.clone()).with_id(new.id());
+                assert!(game.do_action(Action::Upgrade(sacrifice, old, forged)).is_err());
+            },
+            other => panic!("expected an upgrade, got {:?}", other),
+        }
+
+        assert_eq!(game.action_stack.len(), 4);
+        assert_eq!(game.turn, 0);
+    }
+
+    #[test]
+    fn actions_name_pieces_exactly() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+
+        // a piece placed again under an old id, and a piece that is not the one on the board
+        let reused = Action::Place(Piece::new(1, 0, 0).with_id(1));
+        assert_eq!(game.do_action(reused), Err(RuleError::MalformedAction("a placed piece needs an id no piece has had yet")));
+        let p = game.get_piece(0, 0).cloned().unwrap();
+        let doctored = Piece::from_parts(0, 0, 0, 2, p.poss_moves.clone()).with_id(p.id());
+        assert_eq!(game.do_action(Action::Move(doctored, 1, 0)),
+                   Err(RuleError::MalformedAction("the piece is not as it stands on the board")));
+
+        // undoing a place frees its id for redoing it or placing another piece
+        place(&mut game, 1, 0);
+        game.undo_last();
+        assert_eq!(game.place(2, 0, 0).unwrap().pieces()[0].id(), 3);
+        game.redo_last().unwrap();
+        assert_eq!(game.get_piece(1, 0).map(|p| p.id()), Some(3));
+        assert!(game.verify_history().is_ok());
+    }
+
+    #[test]
+    fn zones_hold_for_every_action() {
+        // a board this short still has home rows, and an empty region for the third team
+        let mut game = Game::new(3, 3);
+        let outside = Action::Place(Piece::new(0, 2, 0).with_id(1));
+
+        assert_eq!(game.check_action(&outside), Err(RuleError::OutsidePlacementZone(0)));
+        assert_eq!(game.do_action(outside), Err(RuleError::OutsidePlacementZone(0)));
+        assert_eq!(game.check_action(&Action::Pass(3)), Err(RuleError::MalformedAction("the action belongs to no team")));
+        place(&mut game, 0, 1);
+        assert_eq!(game.place(0, 0, 1).err(), Some(RuleError::OutsidePlacementZone(1)));
+    }
+
+    #[test]
+    fn timeouts_go_through_the_history() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        game.time_out();
+        assert!(game.teams[1].eliminated);
+        assert_eq!(game.winners(), vec![0]);
+
+        let loaded = save::read_game(&save::write_game(&game)).unwrap();
+        assert_eq!(loaded.teams, game.teams);
+        assert!(game.verify_history().is_ok());
+
+        game.undo_last();
+        assert!(!game.teams[1].eliminated);
+        assert_eq!(game.turn, 1);
+    }
+
+    #[test]
+    fn upgrade_over_the_cap_is_illegal() {
+        let mut game = two_teams();
+        let big = Piece::from_parts(0, 0, 0, MAX_LEVEL, MoveDirSet::from(vec![(1, 0), (-1, 0)]));
+        game.board.insert(big).unwrap();
+        game.board.insert(Piece::new(1, 0, 0)).unwrap();
+
+        let small = game.get_piece(1, 0).cloned().unwrap();
+        assert_eq!(game.check_move(&small, 0, 0, true).err(), Some(RuleError::LevelTooHigh(MAX_LEVEL + 1)));
+        assert_eq!(game.check_move(&small, 3, 0, true).err(), Some(RuleError::IllegalDirection(2, 0)));
+    }
+
+    #[test]
+    fn upgrades_without_a_new_direction_fail() {
+        let mut game = two_teams();
+        game.board.insert(Piece::from_parts(0, 0, 0, 1, MoveDirSet::from(vec![(1, 0)]))).unwrap();
+        game.board.insert(Piece::from_parts(1, 0, 0, 1, MoveDirSet::from(vec![(-1, 0)]))).unwrap();
+
+        // the board still shows the merge, which only fails once it is rolled
+        let p = game.get_piece(0, 0).cloned().unwrap();
+        assert_eq!(game.reach(Some(&p), 1, 0), Some(Reach::Upgrade));
+        assert_eq!(game.check_move(&p, 1, 0, true).err(), Some(RuleError::NoNewDirection));
+    }
+
+    #[test]
+    fn upgrades_roll_from_the_seed() {
+        let roll = |seed| {
+            let mut game = two_teams().with_seed(seed);
+            place(&mut game, 0, 0);
+            place(&mut game, 4, 4);
+            place(&mut game, 1, 0);
+            place(&mut game, 4, 3);
+            let p = game.get_piece(1, 0).cloned().unwrap();
+            game.check_move(&p, 0, 0, true).unwrap().pieces()[2].clone()
+        };
+
+        assert_eq!(roll(3), roll(3));
+    }
+
+    #[test]
+    fn allies_share_the_board() {
+        let teams = vec![Alliance::new("A", 5, TEAMS[0]), Alliance::new("B", 5, TEAMS[1]), Alliance::new("C", 5, TEAMS[2])];
+        let mut game = Game::from_vec(5, 5, Vec::new(), teams);
+
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 1);
+        place(&mut game, 4, 4);
+        // diplomacy leaves the turn with the team
+        game.do_action(Action::Ally(0, 1)).unwrap();
+        assert!(!game.allied(0, 1));
+        assert_eq!(game.turn, 0);
+        game.do_action(Action::Pass(0)).unwrap();
+        game.do_action(Action::Ally(1, 0)).unwrap();
+        assert!(game.allied(0, 1));
+        game.do_action(Action::Pass(1)).unwrap();
+        assert_eq!(game.do_action(Action::Ally(2, 2)), Err(RuleError::MalformedAction("diplomacy needs two different teams")));
+        game.do_action(Action::Pass(2)).unwrap();
+
+        let a = game.get_piece(0, 0).cloned().unwrap();
+        let b = game.get_piece(0, 1).cloned().unwrap();
+        assert_eq!(game.check_move(&a, 0, 1, true).err(), Some(RuleError::CaptureAlly(1)));
+        let capture = Action::Composite(Composite::Capture(Arc::new(CaptureVal::from([
+            Action::Remove(b), Action::Move(a.clone(), 0, 1),
+        ]))));
+        assert_eq!(game.do_action(capture), Err(RuleError::CaptureAlly(1)));
+        game.rules = Rc::new(rules::Merging);
+        match game.check_move(&a, 0, 1, true) {
+            Ok(Action::Upgrade(_, _, new)) => assert_eq!(new.team(), 1),
+            other => panic!("expected an upgrade, got {:?}", other),
+        }
+
+        // allies win together once everyone else is out
+        game.teams[2].eliminated = true;
+        assert_eq!(game.winners(), vec![0, 1]);
+        game.teams[2].eliminated = false;
+
+        game.do_action(Action::Break(0, 1)).unwrap();
+        assert!(!game.allied(0, 1));
+        assert_eq!(game.do_action(Action::Break(0, 2)), Err(RuleError::NotOffered(2)));
+        game.undo_last();
+        assert!(game.allied(0, 1));
+        assert_eq!(game.turn, 0);
+        assert!(game.verify_history().is_ok());
+    }
+}
@@ -2,18 +2,37 @@ use std::ops::{Shl, Index, Add};
 use std::fmt::{Display, Formatter, Result};
 use std::result;
 
-use rand::{Rng, thread_rng};
+use rand::{Rng, RngCore};
+
+use game::zone::PlacementZone;
+use game::error::RuleError;
+use game::board::Board;
+
+// the highest level a piece can be upgraded to under the standard rules
+pub const MAX_LEVEL: u32 = 6;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Alliance {
     pub color: [f32; 4],
     pub name: String,
-    pub pieces_left: u32
+    pub pieces_left: u32,
+    // an empty list means the team may place anywhere
+    pub zones: Vec<PlacementZone>,
+    pub eliminated: bool
 }
 
 impl Alliance {
     pub fn new(name: &str, pieces_left: u32, color: [f32; 4]) -> Self {
-        Self { name: String::from(name), color, pieces_left }
+        Self { name: String::from(name), color, pieces_left, zones: Vec::new(), eliminated: false }
+    }
+
+    pub fn with_zones(mut self, zones: Vec<PlacementZone>) -> Self {
+        self.zones = zones;
+        self
+    }
+
+    pub fn can_place_at(&self, x: u32, y: u32, team: usize, board: &Board) -> bool {
+        self.zones.is_empty() || self.zones.iter().any(|z| z.contains(x, y, team, board))
     }
 }
 
@@ -25,6 +44,8 @@ impl Display for Alliance {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Piece {
+    // given when the piece is placed and kept through its moves and upgrades, 0 until then
+    id: u32,
     x: i32, y: i32, team: usize,
     pub poss_moves: MoveDirSet, level: u32
 }
@@ -32,50 +53,78 @@ pub struct Piece {
 impl Piece {
     pub fn new(x: u16, y: u16, team: usize) -> Self {
         Self {
-            x: x as i32, y: y as i32, team, level: 1,
+            id: 0, x: x as i32, y: y as i32, team, level: 1,
             poss_moves: MoveDirSet::from(vec![(1,0), (-1,0), (0,1), (0,-1)])
         }
     }
 
+    pub fn from_parts(x: i32, y: i32, team: usize, level: u32, poss_moves: MoveDirSet) -> Self {
+        Self { id: 0, x, y, team, level, poss_moves }
+    }
+
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = id;
+        self
+    }
+
     pub fn apply(&mut self, m: Move) {
         self.x += m.dx;
         self.y += m.dy;
     }
 
-    pub fn upgrade(&mut self, other: &Piece) -> result::Result<(), String> {
-        if self.level + other.level > 6 {
-            return Err(format!("that level is too high to control"));
+    /// merges `other` into this piece, with `rng` picking each new direction
+    pub fn upgrade(&mut self, other: &Piece, max_level: u32, rng: &mut dyn RngCore) -> result::Result<(), RuleError> {
+        if self.level + other.level > max_level {
+            return Err(RuleError::LevelTooHigh(self.level + other.level));
         }
 
         let mut poss_moves = self.poss_moves.clone();
-        let mut fail = false;
 
         for om in other.poss_moves.clone() {
             let mut vec = vec![];
 
             for pm in self.poss_moves.clone() {
-                let tm = om.clone() + pm.clone();
+                let tm = om + pm;
                 if (tm.dx() != 0 || tm.dy() != 0) && poss_moves.clone().into_iter().position(|m| m == tm).is_none() {
                     vec.push(tm);
                 }
             }
-            if vec.len() == 0 {
-                fail = true;
-            } else {
-                let index: f64 = thread_rng().gen();
-                let index = (vec.len() as f64 * index) as usize;
-                poss_moves.moves.push(vec[index]);
+            if vec.is_empty() {
+                return Err(RuleError::NoNewDirection);
             }
+
+            let index: f64 = rng.gen();
+            let index = (vec.len() as f64 * index) as usize;
+            poss_moves.moves.push(vec[index]);
         }
-        if fail {
-            self.upgrade(other)?;
-        } else {
-            self.level += other.level;
-            self.poss_moves = poss_moves;
-        }
+
+        self.level += other.level;
+        self.poss_moves = poss_moves;
         Ok(())
     }
 
+    /// whether `new` is a piece `upgrade` could have made by merging `other` into this one,
+    /// with one new direction for each of `other`'s, in order
+    pub fn could_become(&self, other: &Piece, new: &Piece) -> bool {
+        let kept = self.poss_moves.moves.len();
+
+        if (new.id, new.x, new.y, new.team, new.level) != (self.id, self.x, self.y, self.team, self.level + other.level)
+            || new.poss_moves.moves.len() != kept + other.poss_moves.moves.len()
+            || new.poss_moves.moves[..kept] != self.poss_moves.moves[..] {
+            return false;
+        }
+
+        other.poss_moves.moves.iter().zip(&new.poss_moves.moves[kept..]).enumerate().all(|(i, (&om, &added))| {
+            (added.dx != 0 || added.dy != 0)
+                && !new.poss_moves.moves[..kept + i].contains(&added)
+                && self.poss_moves.moves.iter().any(|&pm| om + pm == added)
+        })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
     pub fn x(&self) -> i32 {
         self.x
     }
@@ -99,11 +148,11 @@ impl Piece {
 
 impl Display for Piece {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "piece @({}, {}, {}) -> {}", self.x, self.y, self.team, self.poss_moves)
+        write!(f, "piece #{} @({}, {}, {}) -> {}", self.id, self.x, self.y, self.team, self.poss_moves)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MoveDir {
     dx: i32, dy: i32
 }
@@ -138,7 +187,7 @@ impl Display for MoveDir {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MoveDirSet {
     moves: Vec<MoveDir>
 }
@@ -150,6 +199,10 @@ impl MoveDirSet {
         }
     }
 
+    pub fn moves(&self) -> &[MoveDir] {
+        &self.moves
+    }
+
     pub fn from(del: Vec<(i32, i32)>) -> Self {
         let mut set = Self::new();
 
@@ -246,12 +299,31 @@ impl Move {
 
 #[cfg(test)]
 pub mod test {
+    use super::*;
+    use game::player::seeded;
 
     #[test]
     fn new() {
-        let p = super::Piece::new(3, 1);
+        let p = super::Piece::new(3, 1, 0);
 
         assert_eq!(p.x, 3);
         assert_eq!(p.y, 1);
     }
+
+    #[test]
+    fn upgrades_can_be_checked() {
+        let target = Piece::new(2, 2, 0).with_id(1);
+        let sacrifice = Piece::new(2, 3, 0).with_id(2);
+        let mut new = target.clone();
+        new.upgrade(&sacrifice, MAX_LEVEL, &mut seeded(1)).unwrap();
+
+        assert!(target.could_become(&sacrifice, &new));
+        assert!(!target.could_become(&sacrifice, &new.clone().with_id(2)));
+        assert!(!target.could_become(&sacrifice, &Piece::from_parts(2, 2, 0, 3, new.poss_moves.clone()).with_id(1)));
+
+        // a direction that no sum of the two could make
+        let mut forged = new.clone();
+        forged.poss_moves.moves[4] = MoveDir::new(5, 5);
+        assert!(!target.could_become(&sacrifice, &forged));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,118 @@
+//! Where the parts of a piece go inside its unit tile, for a piece of any level
+//!
+//! The body of a piece is drawn in the middle of its tile. Up to `RING` levels above the
+//! first are counted by pips in a ring around it, and higher levels are written across the
+//! body in seven segment digits, so that nothing depends on the font or the tile size.
+
+// the body of every piece, in the team's marker shape
+pub const BODY: [f64; 4] = [0.3, 0.3, 0.4, 0.4];
+
+// the most pips that fit around the body
+const RING: u32 = 8;
+
+// the segments lit for each digit, from bit 0 to 6: top, top right, bottom right, bottom,
+// bottom left, top left and middle
+const DIGITS: [u8; 10] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mark {
+    // a circle in the team's colour
+    Pip([f64; 4]),
+    // a segment of a digit, in the theme's text colour
+    Bar([f64; 4]),
+}
+
+/// the marks showing `level` around and over the body
+pub fn level_marks(level: u32) -> Vec<Mark> {
+    let count = level.saturating_sub(1);
+
+    if count <= RING {
+        ring(count)
+    } else {
+        digits(level)
+    }
+}
+
+// `count` pips on the corners, spread further around the ring as there are more of them
+fn ring(count: u32) -> Vec<Mark> {
+    use std::f64::consts::PI;
+
+    let (radius, size) = if count <= 4 { (0.3 * 2f64.sqrt(), 0.2) } else { (0.38, 0.16) };
+    let step = 2.0 * PI / count.max(4) as f64;
+
+    (0..count).map(|i| {
+        // clockwise from the top left corner
+        let angle = -0.75 * PI + step * i as f64;
+        let (x, y) = (0.5 + radius * angle.cos(), 0.5 + radius * angle.sin());
+        Mark::Pip([x - size / 2.0, y - size / 2.0, size, size])
+    }).collect()
+}
+
+fn digits(level: u32) -> Vec<Mark> {
+    let text = level.to_string();
+    let n = text.len() as f64;
+    let gap = 0.03;
+
+    let width = ((BODY[2] - 0.04 - gap * (n - 1.0)) / n).min(0.14);
+    let (height, stroke) = ((width * 1.8).min(0.26), width / 4.0);
+    let mut x = 0.5 - (width * n + gap * (n - 1.0)) / 2.0;
+    let y = 0.5 - height / 2.0;
+    let mut marks = Vec::new();
+
+    for digit in text.bytes().map(|b| (b - b'0') as usize) {
+        let half = height / 2.0;
+        let segments = [
+            [x, y, width, stroke],
+            [x + width - stroke, y, stroke, half],
+            [x + width - stroke, y + half, stroke, half],
+            [x, y + height - stroke, width, stroke],
+            [x, y + half, stroke, half],
+            [x, y, stroke, half],
+            [x, y + half - stroke / 2.0, width, stroke],
+        ];
+
+        for (i, &segment) in segments.iter().enumerate() {
+            if DIGITS[digit] & (1 << i) != 0 {
+                marks.push(Mark::Bar(segment));
+            }
+        }
+        x += width + gap;
+    }
+
+    marks
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn rect(mark: &Mark) -> [f64; 4] {
+        match *mark {
+            Mark::Pip(r) | Mark::Bar(r) => r,
+        }
+    }
+
+    #[test]
+    fn every_level_fits_its_tile() {
+        for level in 1..2000 {
+            let marks = level_marks(level);
+            assert!(!marks.is_empty() || level == 1);
+
+            for r in marks.iter().map(rect) {
+                assert!(r[0] >= 0.0 && r[1] >= 0.0 && r[0] + r[2] <= 1.0 && r[1] + r[3] <= 1.0, "level {}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn pips_count_then_digits_spell_the_level() {
+        // the first pip sits in the top left corner, where it always has
+        let first = rect(&level_marks(2)[0]);
+        assert!((first[0] - 0.1).abs() < 1e-9 && (first[1] - 0.1).abs() < 1e-9);
+        assert_eq!(level_marks(RING + 1).len(), RING as usize);
+
+        // 1 lights two segments and 0 six
+        assert_eq!(level_marks(10).len(), 8);
+        assert!(level_marks(10).iter().all(|m| matches!(m, Mark::Bar(_))));
+    }
+}
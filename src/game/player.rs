@@ -0,0 +1,146 @@
+//! Computer players, which pick one of the legal actions for the team to move
+
+use std::time::Duration;
+use std::sync::mpsc::Receiver;
+
+use rand::{Rng, SeedableRng};
+use rand::prng::XorShiftRng;
+
+use game::Game;
+use game::action::*;
+use game::engine::EnginePlayer;
+
+pub trait Player {
+    fn name(&self) -> &str;
+    /// an error forfeits the game, for players that can fail like external engines
+    fn choose(&mut self, game: &Game) -> Result<Action, String>;
+
+    /// starts choosing on another thread and answers through the receiver, for players that
+    /// would hold up the window while they think; `None` for players that answer right away
+    fn choose_later(&mut self, _game: &Game) -> Option<Receiver<Result<Action, String>>> {
+        None
+    }
+}
+
+/// a generator that gives the same choices for the same seed
+pub fn seeded(seed: u64) -> XorShiftRng {
+    let mut bytes = [0; 16];
+
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (seed >> ((i % 8) * 8)) as u8 ^ (i as u8).wrapping_mul(0x9d);
+    }
+
+    XorShiftRng::from_seed(bytes)
+}
+
+/// Plays any legal action
+pub struct RandomPlayer {
+    rng: XorShiftRng,
+}
+
+impl RandomPlayer {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: seeded(seed) }
+    }
+}
+
+impl Player for RandomPlayer {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn choose(&mut self, game: &Game) -> Result<Action, String> {
+        let mut actions = game.legal_actions();
+        let i = self.rng.gen_range(0, actions.len());
+
+        Ok(actions.swap_remove(i))
+    }
+}
+
+/// Captures the biggest piece it can, otherwise upgrades, places and moves, in that order
+pub struct GreedyPlayer {
+    rng: XorShiftRng,
+}
+
+impl GreedyPlayer {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: seeded(seed) }
+    }
+
+    fn score(action: &Action) -> u32 {
+        match *action {
+            Action::Composite(Composite::Capture(ref c)) => match c.remove_action {
+                Action::Remove(ref victim) => 10 + victim.level(),
+                _ => 10,
+            },
+            Action::Upgrade(..) => 3,
+            Action::Place(_) => 2,
+            Action::Move(..) => 1,
+            Action::Remove(_) | Action::Pass(_) | Action::Ally(_, _) | Action::Break(_, _) |
+            Action::Forfeit(_) => 0,
+        }
+    }
+}
+
+impl Player for GreedyPlayer {
+    fn name(&self) -> &str {
+        "greedy"
+    }
+
+    fn choose(&mut self, game: &Game) -> Result<Action, String> {
+        let actions = game.legal_actions();
+        let best = actions.iter().map(GreedyPlayer::score).max().unwrap_or(0);
+        let mut best: Vec<Action> = actions.into_iter().filter(|a| GreedyPlayer::score(a) == best).collect();
+        let i = self.rng.gen_range(0, best.len());
+
+        Ok(best.swap_remove(i))
+    }
+}
+
+pub const PLAYERS: [&str; 2] = ["random", "greedy"];
+// `engine:<command>` runs an external engine
+pub const ENGINE: &str = "engine:";
+
+pub fn is_player(name: &str) -> bool {
+    PLAYERS.contains(&name) || name.starts_with(ENGINE)
+}
+
+pub fn by_name(name: &str, seed: u64, timeout: Duration) -> Result<Box<dyn Player>, String> {
+    match name {
+        "random" => Ok(Box::new(RandomPlayer::new(seed))),
+        "greedy" => Ok(Box::new(GreedyPlayer::new(seed))),
+        _ if name.starts_with(ENGINE) => Ok(Box::new(EnginePlayer::from_command(&name[ENGINE.len()..], timeout)?)),
+        _ => Err(format!("unknown player {}, players are {} or {}<command>", name, PLAYERS.join(", "), ENGINE)),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, place};
+
+    #[test]
+    fn greedy_takes_the_capture() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 1);
+
+        let action = GreedyPlayer::new(1).choose(&game).unwrap();
+        assert_eq!(action.team(), 0);
+        match action {
+            Action::Composite(_) => (),
+            _ => panic!("expected a capture, got {:?}", action),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_choices() {
+        let mut game = two_teams();
+        place(&mut game, 2, 2);
+
+        let (mut a, mut b) = (RandomPlayer::new(7), RandomPlayer::new(7));
+        for _ in 0..10 {
+            assert_eq!(format!("{:?}", a.choose(&game).unwrap()), format!("{:?}", b.choose(&game).unwrap()));
+        }
+    }
+}
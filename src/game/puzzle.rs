@@ -0,0 +1,324 @@
+//! Puzzles: a position, a goal and the moves that reach it
+//!
+//! A puzzle file starts with a few lines of its own, followed by the position in the save
+//! file format, with the side to solve it as the team to move:
+//!
+//! ```text
+//! puzzle <title>
+//! goal <what to do, for the player to read>
+//! line <step> <step> ..           one line of the solution, alternating the solver's moves
+//!                                 and the replies; lines sharing a start form a tree
+//! seed <n>                        what upgrades roll from, 0 if missing
+//! chess_like 2
+//! ..
+//! ```
+//!
+//! a step is `x,y>x,y` to move from one square to another, `x,y` to place on a square or
+//! `pass`. Where the tree branches on a reply, the reply of the first line is played. Upgrades
+//! roll from the seed, so they make the same pieces when checking the solution and in play.
+
+use std::fs::File;
+use std::io::Read;
+use std::sync::atomic::AtomicPtr;
+
+use piston_window::*;
+
+use gui::{App, Data, AppGraphics};
+use game::{Game, save, HUD_FONT_SIZE};
+use game::action::Action;
+use game::theme::Theme;
+
+// height in pixels of the text below the board
+const FOOTER_HEIGHT: u32 = 56;
+
+// the squares an action leaves from and arrives on, as in `Action::squares`
+type Step = Vec<(i32, i32)>;
+
+fn read_step(s: &str) -> Result<Step, String> {
+    let square = |s: &str| {
+        let mut xy = s.split(',').map(|n| n.parse::<i32>());
+        match (xy.next(), xy.next(), xy.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => Ok((x, y)),
+            _ => Err(format!("invalid square `{}`", s)),
+        }
+    };
+
+    if s == "pass" {
+        Ok(Vec::new())
+    } else {
+        s.split('>').map(square).collect()
+    }
+}
+
+fn write_step(step: &Step) -> String {
+    if step.is_empty() {
+        return String::from("pass");
+    }
+    step.iter().map(|&(x, y)| format!("{},{}", x, y)).collect::<Vec<_>>().join(">")
+}
+
+// plays `step` for the team to move
+fn play(game: &mut Game, step: &Step) -> Result<(), String> {
+    let turn = game.turn;
+    let action = match step[..] {
+        [] => Ok(Action::Pass(turn)),
+        [(x, y)] => game.place(x as u32, y as u32, turn),
+        [(x, y), (to_x, to_y)] => match game.get_piece(x as u32, y as u32).cloned() {
+            Some(p) => game.check_move(&p, to_x as u32, to_y as u32, true),
+            None => return Err(format!("there is no piece on ({}, {})", x, y)),
+        },
+        _ => return Err(format!("`{}` is not a step", write_step(step))),
+    };
+
+    action.and_then(|action| game.do_action(action)).map_err(|e| e.to_string())
+}
+
+/// Checks the player's moves against the solution as they play
+pub struct Puzzle {
+    pub title: String,
+    pub goal: String,
+    game: Game,
+    lines: Vec<Vec<Step>>,
+    // the steps played so far, all on some line of the solution
+    played: Vec<Step>,
+    feedback: String,
+}
+
+impl Puzzle {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (mut title, mut goal, mut lines) = (String::new(), String::new(), Vec::new());
+        let mut seed = 0;
+        let mut position = "";
+        let mut offset = 0;
+
+        for (number, line) in s.split('\n').enumerate() {
+            let mut words = line.splitn(2, ' ');
+            let rest = words.next().and(words.next()).unwrap_or("").trim();
+
+            match line.split_whitespace().next() {
+                Some("puzzle") => title = String::from(rest),
+                Some("goal") => goal = String::from(rest),
+                Some("line") => lines.push(rest.split_whitespace().map(read_step).collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("line {}: {}", number + 1, e))?),
+                Some("seed") => seed = rest.parse().map_err(|_| format!("line {}: invalid seed `{}`", number + 1, rest))?,
+                None => (),
+                _ => {
+                    position = &s[offset..];
+                    break;
+                },
+            }
+            offset += line.len() + 1;
+        }
+
+        if lines.is_empty() {
+            return Err(String::from("the puzzle has no solution"));
+        }
+
+        // every line has to play out from the position
+        for (i, line) in lines.iter().enumerate() {
+            let mut game = save::read_game(position)?.with_seed(seed);
+            for step in line {
+                play(&mut game, step).map_err(|e| format!("solution {} at {}: {}", i + 1, write_step(step), e))?;
+            }
+        }
+
+        let mut game = save::read_game(position)?.with_seed(seed);
+        game.footer = FOOTER_HEIGHT;
+        let feedback = goal.clone();
+
+        Ok(Self { title, goal, game, lines, played: Vec::new(), feedback })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut s = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut s))
+            .map_err(|e| format!("could not load {}: {}", path, e))?;
+
+        Puzzle::parse(&s)
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.game.theme = theme;
+        self
+    }
+
+    // the steps that follow the ones played on some line
+    fn next_steps(&self) -> Vec<&Step> {
+        let mut steps: Vec<&Step> = Vec::new();
+
+        for line in self.lines.iter().filter(|l| l.starts_with(&self.played)) {
+            if let Some(step) = line.get(self.played.len()) {
+                if !steps.contains(&step) {
+                    steps.push(step);
+                }
+            }
+        }
+
+        steps
+    }
+
+    pub fn is_solved(&self) -> bool {
+        !self.played.is_empty() && self.next_steps().is_empty()
+    }
+
+    // checks the action the player just made, taking it back if it is not on the solution
+    fn judge(&mut self) {
+        let step = match self.game.action_stack.last() {
+            Some(action) => action.squares(),
+            None => return,
+        };
+
+        if !self.next_steps().contains(&&step) {
+            self.game.undo_last();
+            self.feedback = format!("{} is not it, try again", write_step(&step));
+            return;
+        }
+        self.played.push(step);
+
+        if let Some(reply) = self.next_steps().first().map(|&s| s.clone()) {
+            if let Err(msg) = play(&mut self.game, &reply) {
+                println!("err = {}", msg);
+            }
+            self.feedback = format!("good, the reply is {}", write_step(&reply));
+            self.played.push(reply);
+        }
+
+        if self.is_solved() {
+            self.feedback = String::from("solved! R to start over");
+        }
+    }
+
+    fn restart(&mut self) {
+        while !self.game.action_stack.is_empty() {
+            self.game.undo_last();
+        }
+        self.played.clear();
+        self.feedback = self.goal.clone();
+    }
+
+    // passes a click or a key on to the game, then judges whatever it played
+    fn play_input<F: FnOnce(&mut Game)>(&mut self, input: F) {
+        if self.is_solved() {
+            return;
+        }
+
+        let before = self.game.action_stack.len();
+        input(&mut self.game);
+
+        if self.game.action_stack.len() > before {
+            self.judge();
+        }
+    }
+
+    fn hint(&mut self) {
+        self.feedback = match self.next_steps().first().and_then(|s| s.first()) {
+            Some(&(x, y)) => format!("try the square ({}, {})", x, y),
+            None => String::from("nothing left to find, R to start over"),
+        };
+    }
+}
+
+impl App for Puzzle {
+    fn set_data(&mut self, data: AtomicPtr<Data>) {
+        self.game.set_data(data);
+    }
+
+    fn set_window(&mut self, window: AtomicPtr<PistonWindow>) {
+        self.game.set_window(window);
+    }
+
+    fn render(&self, c: Context, g: &mut AppGraphics) {
+        self.game.render(c, g);
+
+        let y = self.game.footer_top() + HUD_FONT_SIZE as f64 + 4.0;
+
+        self.game.draw_text(self.game.theme.text, &self.title, 16.0, y, c, g);
+        self.game.draw_text(self.game.theme.text, &format!("{}   (H for a hint)", self.feedback), 16.0, y + HUD_FONT_SIZE as f64 + 8.0, c, g);
+    }
+
+    fn update(&mut self, args: &UpdateArgs) {
+        self.game.animator.update(args.dt);
+        self.game.wait_repeat(args.dt);
+    }
+
+    fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::R => self.restart(),
+            Key::H => self.hint(),
+            _ => self.play_input(|game| game.press(Button::Keyboard(key))),
+        }
+    }
+
+    fn handle_key_held(&mut self, key: Key) {
+        self.play_input(|game| game.hold(Button::Keyboard(key)));
+    }
+
+    fn handle_mouse(&mut self, mouse_button: MouseButton, mouse_x: f64, mouse_y: f64) {
+        self.play_input(|game| game.handle_mouse(mouse_button, mouse_x, mouse_y));
+    }
+
+    fn mouse_moved(&mut self, args: &Motion) {
+        self.game.mouse_moved(args);
+    }
+
+    fn handle_cursor(&mut self, cursor: bool) {
+        self.game.handle_cursor(cursor);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    const FIRST: &str = include_str!("../res/puzzles/first-capture.puzzle");
+
+    fn try_step(puzzle: &mut Puzzle, step: &str) {
+        play(&mut puzzle.game, &read_step(step).unwrap()).unwrap();
+        puzzle.judge();
+    }
+
+    #[test]
+    fn checks_moves_against_the_solution() {
+        let mut puzzle = Puzzle::parse(FIRST).unwrap();
+        assert_eq!(puzzle.title, "First capture");
+
+        try_step(&mut puzzle, "0,0>1,0");
+        assert!(puzzle.game.action_stack.is_empty());
+        assert!(puzzle.feedback.contains("try again"));
+
+        try_step(&mut puzzle, "0,0>0,1");
+        assert_eq!(puzzle.game.action_stack.len(), 2);
+        assert!(!puzzle.is_solved());
+
+        try_step(&mut puzzle, "0,1>0,2");
+        assert!(puzzle.is_solved());
+        assert_eq!(puzzle.game.get_piece(0, 2).map(|p| p.team()), Some(0));
+
+        puzzle.restart();
+        assert!(puzzle.game.get_piece(0, 0).is_some() && puzzle.played.is_empty());
+    }
+
+    #[test]
+    fn upgrades_play_as_they_were_checked() {
+        let merge = FIRST.replace("line 0,0>0,1 4,4>3,4 0,1>0,2", "line 1,0>0,0\nseed 5")
+            .replace("piece #3", "piece #4 0 1 0 1 1 -1 0\npiece #3");
+        let upgraded = || {
+            let mut puzzle = Puzzle::parse(&merge).unwrap();
+            try_step(&mut puzzle, "1,0>0,0");
+            assert!(puzzle.is_solved());
+            puzzle.game.get_piece(0, 0).cloned().unwrap()
+        };
+
+        assert_eq!(upgraded().level(), 4);
+        assert_eq!(upgraded(), upgraded());
+    }
+
+    #[test]
+    fn solutions_must_play_out() {
+        let broken = FIRST.replace("0,1>0,2", "0,1>0,3");
+        assert!(Puzzle::parse(&broken).err().unwrap_or_default().contains("solution 1"));
+        assert!(read_step("1,2>x").is_err());
+    }
+}
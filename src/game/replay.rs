@@ -0,0 +1,243 @@
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use piston_window::*;
+
+use gui::{App, Data, AppGraphics, unwrap};
+use game::{Game, save, HUD_FONT_SIZE};
+use game::theme::Theme;
+
+// height in pixels of the timeline below the board
+const TIMELINE_HEIGHT: u32 = 56;
+const BAR_MARGIN: f64 = 16.0;
+const BAR_HEIGHT: f64 = 8.0;
+const KNOB: f64 = 14.0;
+
+const BAR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+const HIGHLIGHT: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Steps through a recorded game, one action at a time
+pub struct Replay {
+    game: Game,
+    // how many actions of the history have been applied
+    position: usize,
+    total: usize,
+    playing: bool,
+    speed: usize,
+    // seconds since the last step while playing
+    timer: f64,
+    mouse_x: f64,
+    dragging: bool,
+    data: AtomicPtr<Data>,
+}
+
+impl Replay {
+    /// rewinds `game` to its first action, so its whole history can be replayed
+    pub fn new(mut game: Game) -> Self {
+        let total = game.action_stack.len();
+
+        for _ in 0..total {
+            game.undo_last();
+        }
+        game.footer = TIMELINE_HEIGHT;
+
+        Self {
+            game, position: 0, total,
+            playing: false, speed: 2, timer: 0.0,
+            mouse_x: 0.0, dragging: false,
+            data: AtomicPtr::default(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        save::load_from_file(path).map(Replay::new)
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.game.theme = theme;
+        self
+    }
+
+    fn step_forward(&mut self) {
+        if self.position < self.total && self.game.redo_last().is_ok() {
+            self.position += 1;
+
+            if let Some(action) = self.game.action_stack.last() {
+                self.game.animator.start(action);
+            }
+        }
+    }
+
+    fn step_back(&mut self) {
+        if self.position > 0 {
+            self.game.undo_last();
+            self.position -= 1;
+        }
+    }
+
+    fn seek(&mut self, position: usize) {
+        let position = position.min(self.total);
+
+        while self.position < position {
+            if self.game.redo_last().is_err() {
+                break;
+            }
+            self.position += 1;
+        }
+        while self.position > position {
+            self.step_back();
+        }
+        self.game.animator.clear();
+    }
+
+    // the x coordinates of the ends of the timeline bar
+    fn bar(&self) -> (f64, f64) {
+        let data = unwrap(&self.data);
+
+        (BAR_MARGIN, data.screen_width as f64 - BAR_MARGIN)
+    }
+
+    fn bar_y(&self) -> f64 {
+        self.game.footer_top() + BAR_MARGIN
+    }
+
+    fn seek_to_mouse(&mut self) {
+        let (start, end) = self.bar();
+        let t = ((self.mouse_x - start) / (end - start)).clamp(0.0, 1.0);
+
+        self.seek((t * self.total as f64).round() as usize);
+    }
+}
+
+impl App for Replay {
+    fn set_data(&mut self, data: AtomicPtr<Data>) {
+        self.data = AtomicPtr::new(data.load(Ordering::Relaxed));
+        self.game.set_data(data);
+    }
+
+    fn set_window(&mut self, window: AtomicPtr<PistonWindow>) {
+        self.game.set_window(window);
+    }
+
+    fn render(&self, c: Context, g: &mut AppGraphics) {
+        use graphics::*;
+
+        self.game.render(c, g);
+
+        // outline the squares of the last action
+        if let Some(action) = self.game.action_stack.last() {
+            let transform = self.game.board_transform(c);
+            let outline = Rectangle::new_border(HIGHLIGHT, 0.06);
+
+            for (x, y) in action.squares() {
+                outline.draw([x as f64 + 0.06, y as f64 + 0.06, 0.88, 0.88], &c.draw_state, transform, g);
+            }
+        }
+
+        let (start, end) = self.bar();
+        let y = self.bar_y();
+        let t = if self.total == 0 { 0.0 } else { self.position as f64 / self.total as f64 };
+        let knob = start + (end - start) * t;
+
+        rectangle(BAR, [start, y, end - start, BAR_HEIGHT], c.transform, g);
+        rectangle(self.game.theme.selected, [start, y, knob - start, BAR_HEIGHT], c.transform, g);
+        ellipse(self.game.theme.text, [knob - KNOB / 2.0, y + BAR_HEIGHT / 2.0 - KNOB / 2.0, KNOB, KNOB], c.transform, g);
+
+        let status = format!("{} {}/{}  {}x  {}",
+                             if self.playing { "playing" } else { "paused" },
+                             self.position, self.total, SPEEDS[self.speed],
+                             self.game.action_stack.last().map(|a| self.game.describe(a)).unwrap_or_default());
+        self.game.draw_text(self.game.theme.text, &status, start, y + BAR_HEIGHT + 8.0 + HUD_FONT_SIZE as f64, c, g);
+    }
+
+    fn update(&mut self, args: &UpdateArgs) {
+        self.game.animator.update(args.dt);
+
+        if !self.playing {
+            return;
+        }
+
+        self.timer += args.dt * SPEEDS[self.speed];
+        if self.timer >= 1.0 {
+            self.timer = 0.0;
+            self.step_forward();
+        }
+        if self.position == self.total {
+            self.playing = false;
+        }
+    }
+
+    fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::Right => self.step_forward(),
+            Key::Left => self.step_back(),
+            Key::Home => self.seek(0),
+            Key::End => {
+                let total = self.total;
+                self.seek(total);
+            },
+            Key::Space => {
+                if self.position == self.total {
+                    self.seek(0);
+                }
+                self.playing = !self.playing;
+                self.timer = 0.0;
+            },
+            Key::Up | Key::Equals => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+            Key::Down | Key::Minus => self.speed = self.speed.saturating_sub(1),
+            _ => (),
+        }
+    }
+
+    fn handle_mouse(&mut self, mouse_button: MouseButton, mouse_x: f64, mouse_y: f64) {
+        let y = self.bar_y();
+
+        self.dragging = mouse_button == MouseButton::Left && (mouse_y - y - BAR_HEIGHT / 2.0).abs() <= KNOB;
+        if self.dragging {
+            self.mouse_x = mouse_x;
+            self.playing = false;
+            self.seek_to_mouse();
+        }
+    }
+
+    fn handle_mouse_held(&mut self, mouse_button: MouseButton) {
+        if mouse_button == MouseButton::Left && self.dragging {
+            self.seek_to_mouse();
+        }
+    }
+
+    fn mouse_moved(&mut self, args: &Motion) {
+        if let Motion::MouseCursor(x, _) = *args {
+            self.mouse_x = x;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, act, place};
+
+    #[test]
+    fn seeking_replays_the_history() {
+        let mut game = two_teams();
+
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 2);
+        act(&mut game, (0, 0), (0, 1));
+        let end = game.board().clone();
+
+        let mut replay = Replay::new(game);
+        assert_eq!((replay.position, replay.total), (0, 3));
+        assert_eq!(replay.game.board().iter().count(), 0);
+
+        replay.seek(3);
+        assert_eq!(*replay.game.board(), end);
+
+        replay.seek(1);
+        replay.step_forward();
+        assert_eq!(replay.position, 2);
+        assert_eq!(replay.game.board().iter().count(), 2);
+    }
+}
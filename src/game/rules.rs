@@ -0,0 +1,181 @@
+//! The decisions that differ between variants of the game
+//!
+//! Every method has the standard behaviour as its default, so a variant only overrides
+//! what it changes.
+
+use rand::RngCore;
+
+use game::Game;
+use game::pieces::*;
+use game::error::RuleError;
+
+pub trait Rules {
+    /// the name the variant is saved and selected under
+    fn name(&self) -> &str;
+
+    /// whether `attacker` may capture `defender`
+    fn can_capture(&self, attacker: &Piece, defender: &Piece) -> bool {
+        attacker.level() + 1 >= defender.level()
+    }
+
+    /// the highest level a piece can be upgraded to
+    fn max_level(&self) -> u32 {
+        MAX_LEVEL
+    }
+
+    /// whether a piece may merge into a piece of an allied team
+    fn merge_with_allies(&self) -> bool {
+        false
+    }
+
+    /// the piece made by merging `sacrifice` into `target`, with new directions from `rng`
+    fn upgrade(&self, target: &Piece, sacrifice: &Piece, rng: &mut dyn RngCore) -> Result<Piece, RuleError> {
+        let mut new = target.clone();
+        new.upgrade(sacrifice, self.max_level(), rng)?;
+        Ok(new)
+    }
+
+    /// whether `new` is a piece `upgrade` could make by merging `sacrifice` into `target`,
+    /// for upgrades that were rolled elsewhere, like in a save or by an engine
+    fn check_upgrade(&self, target: &Piece, sacrifice: &Piece, new: &Piece) -> Result<(), RuleError> {
+        if target.could_become(sacrifice, new) {
+            Ok(())
+        } else {
+            Err(RuleError::MalformedAction("the upgraded piece is not one merging the two pieces makes"))
+        }
+    }
+
+    /// whether `team` may place a piece at (x, y), which is on the board and empty
+    fn check_place(&self, game: &Game, x: u32, y: u32, team: usize) -> Result<(), RuleError> {
+        let alliance = &game.teams()[team];
+
+        if alliance.pieces_left == 0 {
+            Err(RuleError::OutOfPlacements(team))
+        } else if !alliance.can_place_at(x, y, team, game.board()) {
+            Err(RuleError::OutsidePlacementZone(team))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// the team to move after `turn`, skipping eliminated teams
+    fn next_turn(&self, teams: &[Alliance], turn: usize) -> usize {
+        let len = teams.len();
+        let mut next = turn;
+
+        for _ in 0..len {
+            next = (next + 1) % len;
+            if !teams[next].eliminated {
+                break;
+            }
+        }
+
+        next
+    }
+}
+
+pub struct Standard;
+
+impl Rules for Standard {
+    fn name(&self) -> &str {
+        "standard"
+    }
+}
+
+/// Captures need at least the defender's level, and pieces stop at level 4
+pub struct Strict;
+
+impl Rules for Strict {
+    fn name(&self) -> &str {
+        "strict"
+    }
+
+    fn can_capture(&self, attacker: &Piece, defender: &Piece) -> bool {
+        attacker.level() >= defender.level()
+    }
+
+    fn max_level(&self) -> u32 {
+        4
+    }
+}
+
+/// Allies may merge their pieces, and the merged piece joins the team it merged into
+pub struct Merging;
+
+impl Rules for Merging {
+    fn name(&self) -> &str {
+        "merging"
+    }
+
+    fn merge_with_allies(&self) -> bool {
+        true
+    }
+}
+
+pub const RULES: [&str; 3] = ["standard", "strict", "merging"];
+
+pub fn by_name(name: &str) -> Result<Box<dyn Rules>, String> {
+    match name {
+        "standard" => Ok(Box::new(Standard)),
+        "strict" => Ok(Box::new(Strict)),
+        "merging" => Ok(Box::new(Merging)),
+        _ => Err(format!("unknown rules {}, rules are {}", name, RULES.join(", "))),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::sync::Arc;
+    use game::action::*;
+    use game::action::composite_vals::*;
+    use game::test::{two_teams, place};
+
+    // the first team takes every turn
+    struct Solo;
+
+    impl Rules for Solo {
+        fn name(&self) -> &str {
+            "solo"
+        }
+
+        fn next_turn(&self, _teams: &[Alliance], _turn: usize) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn strict_captures_only_down() {
+        let capture = |rules: Box<dyn Rules>| {
+            let mut game = two_teams().with_rules(rules);
+            game.board.insert(Piece::from_parts(0, 0, 0, 1, MoveDirSet::from(vec![(0, 1)]))).unwrap();
+            game.board.insert(Piece::from_parts(0, 1, 1, 2, MoveDirSet::from(vec![(0, -1)]))).unwrap();
+
+            let attacker = game.get_piece(0, 0).cloned().unwrap();
+            let defender = game.get_piece(0, 1).cloned().unwrap();
+            let capture = Action::Composite(Composite::Capture(Arc::new(CaptureVal::from([
+                Action::Remove(defender), Action::Move(attacker.clone(), 0, 1),
+            ]))));
+
+            // actions built by hand get the same answer as the board
+            let err = game.check_move(&attacker, 0, 1, true).err();
+            assert_eq!(game.check_action(&capture).err(), err);
+            err
+        };
+
+        assert_eq!(capture(Box::new(Standard)), None);
+        assert_eq!(capture(Box::new(Strict)), Some(RuleError::CaptureNotAllowed(1, 2)));
+    }
+
+    #[test]
+    fn turn_order_can_change() {
+        let mut game = two_teams().with_rules(Box::new(Solo));
+        place(&mut game, 0, 0);
+        place(&mut game, 1, 0);
+        assert_eq!(game.turn, 0);
+        assert_eq!(game.teams[0].pieces_left, 3);
+
+        game.undo_last();
+        assert_eq!(game.turn, 0);
+    }
+}
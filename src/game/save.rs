@@ -0,0 +1,501 @@
+//! A line based text format for saving and loading games
+//!
+//! ```text
+//! chess_like 2
+//! board <width> <height>
+//! turn <team>
+//! rules <name>                                   (standard if missing)
+//! fog <reach|radius>                             (no fog if missing)
+//! clock <loss|pass> <time spent on the current move>
+//! team <pieces left> <eliminated> <r> <g> <b> <a> <name>
+//! zone <rows a b|region x y w h|adjacent>        (belongs to the last team)
+//! timer <remaining> <time control>                (belongs to the last team)
+//! offer <team> <other>                          (an alliance offered by team to other)
+//! wall <x> <y>
+//! piece <piece>
+//! action <action>
+//! ```
+//!
+//! a piece is written as `#id team x y level n dx1 dy1 .. dxn dyn`. Saves from version 1
+//! have no ids, and get them when loaded by following every piece through the history.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::mem;
+
+use game::Game;
+use game::pieces::*;
+use game::action::*;
+use game::action::composite_vals::*;
+use game::zone::PlacementZone;
+use game::clock::*;
+use game::board::Board;
+use game::rules;
+use game::fog::Vision;
+
+const HEADER: &str = "chess_like 2";
+// older versions that can still be read
+const READS: [&str; 2] = ["chess_like 1", HEADER];
+
+fn next<'a, I>(tokens: &mut I) -> Result<&'a str, String>
+    where I: Iterator<Item = &'a str> {
+    tokens.next().ok_or_else(|| String::from("unexpected end of line"))
+}
+
+fn parse<'a, T, I>(tokens: &mut I) -> Result<T, String>
+    where T: FromStr, I: Iterator<Item = &'a str> {
+    let token = next(tokens)?;
+    token.parse().map_err(|_| format!("invalid value `{}`", token))
+}
+
+pub fn write_piece(p: &Piece) -> String {
+    let mut s = format!("#{} {} {} {} {} {}", p.id(), p.team(), p.x(), p.y(), p.level(), p.poss_moves.moves().len());
+
+    for m in p.poss_moves.moves() {
+        s.push_str(&format!(" {} {}", m.dx(), m.dy()));
+    }
+
+    s
+}
+
+pub fn read_piece<'a, I>(tokens: &mut I) -> Result<Piece, String>
+    where I: Iterator<Item = &'a str> {
+    let first = next(tokens)?;
+    let (id, team) = match first.strip_prefix('#') {
+        Some(id) => (id.parse().map_err(|_| format!("invalid id `{}`", first))?, parse(tokens)?),
+        None => (0, first.parse().map_err(|_| format!("invalid value `{}`", first))?),
+    };
+    let x = parse(tokens)?;
+    let y = parse(tokens)?;
+    let level = parse(tokens)?;
+    let count: usize = parse(tokens)?;
+
+    let mut moves = Vec::with_capacity(count);
+    for _ in 0..count {
+        moves.push((parse(tokens)?, parse(tokens)?));
+    }
+
+    Ok(Piece::from_parts(x, y, team, level, MoveDirSet::from(moves)).with_id(id))
+}
+
+pub fn write_action(action: &Action) -> String {
+    match *action {
+        Action::Place(ref p) => format!("place {}", write_piece(p)),
+        Action::Remove(ref p) => format!("remove {}", write_piece(p)),
+        Action::Move(ref p, dx, dy) => format!("move {} {} {}", write_piece(p), dx, dy),
+        Action::Upgrade(ref sacrifice, ref old, ref new) =>
+            format!("upgrade {} {} {}", write_piece(sacrifice), write_piece(old), write_piece(new)),
+        Action::Composite(Composite::Capture(ref c)) =>
+            format!("capture {} {}", write_action(&c.remove_action), write_action(&c.move_action)),
+        Action::Pass(team) => format!("pass {}", team),
+        Action::Ally(team, other) => format!("ally {} {}", team, other),
+        Action::Break(team, other) => format!("break {} {}", team, other),
+        Action::Forfeit(team) => format!("forfeit {}", team),
+    }
+}
+
+pub fn read_action<'a, I>(tokens: &mut I) -> Result<Action, String>
+    where I: Iterator<Item = &'a str> {
+    match next(tokens)? {
+        "place" => Ok(Action::Place(read_piece(tokens)?)),
+        "remove" => Ok(Action::Remove(read_piece(tokens)?)),
+        "move" => {
+            let p = read_piece(tokens)?;
+            Ok(Action::Move(p, parse(tokens)?, parse(tokens)?))
+        },
+        "upgrade" => {
+            let sacrifice = read_piece(tokens)?;
+            let old = read_piece(tokens)?;
+            Ok(Action::Upgrade(sacrifice, old, read_piece(tokens)?))
+        },
+        "capture" => {
+            let remove_action = read_action(tokens)?;
+            let move_action = read_action(tokens)?;
+            Ok(Action::Composite(Composite::Capture(Arc::new(
+                CaptureVal::from([remove_action, move_action])
+            ))))
+        },
+        "pass" => Ok(Action::Pass(parse(tokens)?)),
+        "ally" => Ok(Action::Ally(parse(tokens)?, parse(tokens)?)),
+        "break" => Ok(Action::Break(parse(tokens)?, parse(tokens)?)),
+        "forfeit" => Ok(Action::Forfeit(parse(tokens)?)),
+        other => Err(format!("unknown action `{}`", other))
+    }
+}
+
+fn write_zone(zone: &PlacementZone) -> String {
+    match *zone {
+        PlacementZone::Rows(start, end) => format!("rows {} {}", start, end),
+        PlacementZone::Region(x, y, w, h) => format!("region {} {} {} {}", x, y, w, h),
+        PlacementZone::AdjacentToOwn => String::from("adjacent"),
+    }
+}
+
+fn read_zone<'a, I>(tokens: &mut I) -> Result<PlacementZone, String>
+    where I: Iterator<Item = &'a str> {
+    match next(tokens)? {
+        "rows" => Ok(PlacementZone::Rows(parse(tokens)?, parse(tokens)?)),
+        "region" => Ok(PlacementZone::Region(parse(tokens)?, parse(tokens)?,
+                                             parse(tokens)?, parse(tokens)?)),
+        "adjacent" => Ok(PlacementZone::AdjacentToOwn),
+        other => Err(format!("unknown zone `{}`", other))
+    }
+}
+
+fn read_time_control<'a, I>(tokens: &mut I) -> Result<TimeControl, String>
+    where I: Iterator<Item = &'a str> {
+    match next(tokens)? {
+        "unlimited" => Ok(TimeControl::Unlimited),
+        "sudden" => Ok(TimeControl::SuddenDeath(parse(tokens)?)),
+        "fischer" => Ok(TimeControl::Fischer(parse(tokens)?, parse(tokens)?)),
+        "bronstein" => Ok(TimeControl::Bronstein(parse(tokens)?, parse(tokens)?)),
+        "per-move" => Ok(TimeControl::PerMove(parse(tokens)?)),
+        other => Err(format!("unknown time control `{}`", other))
+    }
+}
+
+pub fn write_game(game: &Game) -> String {
+    let mut lines = vec![
+        String::from(HEADER),
+        format!("board {} {}", game.board.width(), game.board.height()),
+        format!("turn {}", game.turn),
+        format!("rules {}", game.rules.name()),
+        format!("clock {} {}", game.clock.policy, game.clock.spent()),
+    ];
+
+    if let Some(vision) = game.fog {
+        lines.push(format!("fog {}", vision));
+    }
+
+    for (team, timer) in game.teams.iter().zip(game.clock.timers()) {
+        let c = team.color;
+        lines.push(format!("team {} {} {} {} {} {} {}", team.pieces_left, team.eliminated as u8,
+                            c[0], c[1], c[2], c[3], team.name));
+
+        for zone in &team.zones {
+            lines.push(format!("zone {}", write_zone(zone)));
+        }
+
+        lines.push(format!("timer {} {}", timer.remaining, timer.control));
+    }
+
+    for &(team, other) in &game.offers {
+        lines.push(format!("offer {} {}", team, other));
+    }
+
+    for (x, y) in game.board.walls() {
+        lines.push(format!("wall {} {}", x, y));
+    }
+
+    for p in game.board.iter() {
+        lines.push(format!("piece {}", write_piece(p)));
+    }
+
+    for action in &game.action_stack {
+        lines.push(format!("action {}", write_action(action)));
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+pub fn read_game(s: &str) -> Result<Game, String> {
+    let mut lines = s.lines().enumerate();
+
+    match lines.next() {
+        Some((_, header)) if READS.contains(&header) => (),
+        _ => return Err(String::from("not a chess_like save file"))
+    }
+
+    let mut game = Game::from_vec(0, 0, Vec::new(), Vec::new());
+    let (mut width, mut height) = (0, 0);
+    let mut pieces = Vec::new();
+    let mut walls = Vec::new();
+    let mut policy = TimeoutPolicy::Loss;
+    let mut spent = 0.0;
+    let mut timers = Vec::new();
+
+    for (number, line) in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut tokens = line.split_whitespace();
+        let result = match next(&mut tokens)? {
+            "board" => parse(&mut tokens).and_then(|w| {
+                width = w;
+                height = parse(&mut tokens)?;
+                Ok(())
+            }),
+            "turn" => parse(&mut tokens).map(|t| game.turn = t),
+            "rules" => next(&mut tokens).and_then(rules::by_name).map(|r| game.rules = Rc::from(r)),
+            "fog" => next(&mut tokens).and_then(Vision::parse).map(|v| game.fog = Some(v)),
+            "clock" => {
+                policy = match next(&mut tokens)? {
+                    "loss" => TimeoutPolicy::Loss,
+                    "pass" => TimeoutPolicy::Pass,
+                    other => return Err(format!("line {}: unknown timeout policy `{}`", number + 1, other))
+                };
+                parse(&mut tokens).map(|s| spent = s)
+            },
+            "team" => (|| {
+                let pieces_left = parse(&mut tokens)?;
+                let eliminated: u8 = parse(&mut tokens)?;
+                let color = [parse(&mut tokens)?, parse(&mut tokens)?,
+                             parse(&mut tokens)?, parse(&mut tokens)?];
+                let name = tokens.clone().collect::<Vec<_>>().join(" ");
+
+                let mut team = Alliance::new(&name, pieces_left, color);
+                team.eliminated = eliminated != 0;
+                game.teams.push(team);
+                Ok(())
+            })(),
+            "zone" => read_zone(&mut tokens).and_then(|zone| {
+                let team = game.teams.last_mut().ok_or_else(|| String::from("zone before any team"))?;
+                team.zones.push(zone);
+                Ok(())
+            }),
+            "timer" => parse(&mut tokens).and_then(|remaining| {
+                let control = read_time_control(&mut tokens)?;
+                timers.push(Timer { control, remaining });
+                Ok(())
+            }),
+            "offer" => parse(&mut tokens).and_then(|team| {
+                game.offers.insert((team, parse(&mut tokens)?));
+                Ok(())
+            }),
+            "wall" => parse(&mut tokens).and_then(|x| {
+                walls.push((x, parse(&mut tokens)?));
+                Ok(())
+            }),
+            "piece" => read_piece(&mut tokens).map(|p| pieces.push(p)),
+            "action" => read_action(&mut tokens).map(|a| game.action_stack.push(a)),
+            other => Err(format!("unknown entry `{}`", other))
+        };
+
+        if let Err(msg) = result {
+            return Err(format!("line {}: {}", number + 1, msg));
+        }
+    }
+
+    if timers.len() != game.teams.len() {
+        return Err(String::from("every team needs exactly one timer"));
+    }
+    if game.turn >= game.teams.len() {
+        return Err(format!("turn {} is not a team", game.turn));
+    }
+
+    game.board = Board::new(width, height);
+    for (x, y) in walls {
+        game.board.set_wall(x, y, true).map_err(|e| e.to_string())?;
+    }
+    for p in pieces {
+        game.board.insert(p).map_err(|e| e.to_string())?;
+    }
+
+    game.clock = Clock::from_timers(policy, timers, spent);
+    check_ranges(&game)?;
+
+    if game.board.iter().chain(game.action_stack.iter().flat_map(|a| a.pieces())).any(|p| p.id() == 0) {
+        assign_ids(&mut game)?;
+    } else {
+        game.next_id = game.first_free_id();
+    }
+
+    Ok(game)
+}
+
+// every team a save names must exist and every piece have a level, or drawing the game
+// and its statistics would fail long after loading
+fn check_ranges(game: &Game) -> Result<(), String> {
+    let teams = game.teams.len();
+    let offers = game.offers.iter().flat_map(|&(team, other)| vec![team, other]);
+    let diplomacy = game.action_stack.iter().flat_map(|a| match *a {
+        Action::Ally(_, other) | Action::Break(_, other) => vec![a.team(), other],
+        _ => vec![a.team()],
+    });
+
+    if let Some(team) = offers.chain(diplomacy).find(|&t| t >= teams) {
+        return Err(format!("team {} does not exist", team));
+    }
+
+    for p in game.board.iter().chain(game.action_stack.iter().flat_map(|a| a.pieces())) {
+        if p.team() >= teams {
+            return Err(format!("{} belongs to team {}, which does not exist", p, p.team()));
+        }
+        if p.level() == 0 {
+            return Err(format!("{} has level 0", p));
+        }
+    }
+
+    Ok(())
+}
+
+fn new_id(next: &mut u32) -> u32 {
+    *next += 1;
+    *next - 1
+}
+
+// the id of the piece on (x, y), or a new one if there is none
+fn id_at(board: &Board, x: i32, y: i32, next: &mut u32) -> u32 {
+    board.get(x, y).map(|p| p.id()).unwrap_or_else(|| new_id(next))
+}
+
+// a copy of `action` naming pieces by the ids of those it leaves on `board`, with new ids
+// for the pieces it takes off
+fn with_ids(action: &Action, board: &Board, next: &mut u32) -> Action {
+    match *action {
+        Action::Place(ref p) => Action::Place(p.clone().with_id(id_at(board, p.x(), p.y(), next))),
+        Action::Remove(ref p) => Action::Remove(p.clone().with_id(new_id(next))),
+        Action::Move(ref p, dx, dy) => Action::Move(p.clone().with_id(id_at(board, p.x() + dx, p.y() + dy, next)), dx, dy),
+        Action::Upgrade(ref sacrifice, ref old, ref new) => {
+            let id = id_at(board, new.x(), new.y(), next);
+            Action::Upgrade(sacrifice.clone().with_id(new_id(next)), old.clone().with_id(id), new.clone().with_id(id))
+        },
+        Action::Composite(Composite::Capture(ref c)) => Action::Composite(Composite::Capture(Arc::new(CaptureVal::from([
+            with_ids(&c.remove_action, board, next),
+            with_ids(&c.move_action, board, next),
+        ])))),
+        ref other => other.clone(),
+    }
+}
+
+// numbers the pieces of a game saved without ids, stepping back through the history from
+// the final board and then forward again
+fn assign_ids(game: &mut Game) -> Result<(), String> {
+    let mut next = 1;
+    let pieces: Vec<Piece> = game.board.iter().cloned().collect();
+    for p in pieces {
+        game.board.remove_at(p.x(), p.y());
+        game.board.insert(p.with_id(new_id(&mut next))).map_err(|e| e.to_string())?;
+    }
+
+    let mut history = Vec::new();
+    for action in mem::take(&mut game.action_stack).iter().rev() {
+        let action = with_ids(action, &game.board, &mut next);
+        game.undo_action(action.clone()).map_err(|e| e.to_string())?;
+        history.push(action);
+    }
+    history.reverse();
+
+    // renumber in the order the pieces came into the game, starting board first, so every
+    // place takes an id no earlier piece had
+    let mut ids = HashMap::new();
+    let mut fresh = 1;
+    let placed = history.iter().filter_map(|a| match *a {
+        Action::Place(ref p) => Some(p),
+        _ => None,
+    });
+    for p in game.board.iter().chain(placed).chain(history.iter().flat_map(|a| a.pieces())) {
+        ids.entry(p.id()).or_insert_with(|| new_id(&mut fresh));
+    }
+
+    let start: Vec<Piece> = game.board.iter().cloned().collect();
+    for p in start {
+        game.board.remove_at(p.x(), p.y());
+        game.board.insert(p.clone().with_id(ids[&p.id()])).map_err(|e| e.to_string())?;
+    }
+    for action in history {
+        let action = action.map_pieces(&mut |p| p.clone().with_id(ids[&p.id()]));
+        game.apply_action(action.clone()).map_err(|e| e.to_string())?;
+        game.action_stack.push(action);
+    }
+
+    game.next_id = fresh;
+    Ok(())
+}
+
+pub fn save_to_file(game: &Game, path: &str) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut file| file.write_all(write_game(game).as_bytes()))
+        .map_err(|e| format!("could not save to {}: {}", path, e))
+}
+
+pub fn load_from_file(path: &str) -> Result<Game, String> {
+    let mut s = String::new();
+
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut s))
+        .map_err(|e| format!("could not load {}: {}", path, e))?;
+
+    read_game(&s)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, place, act};
+
+    #[test]
+    fn round_trip() {
+        let mut game = Game::new(10, 10).with_rules(Box::new(rules::Strict)).with_fog(Vision::Radius(2));
+        game.board.insert(Piece::new(3, 4, 1).with_id(1)).unwrap();
+        game.board.set_wall(5, 5, true).unwrap();
+        game.action_stack.push(Action::Place(Piece::new(3, 4, 1).with_id(1)));
+        game.action_stack.push(Action::Pass(2));
+        game.action_stack.push(Action::Ally(0, 2));
+        game.offers.insert((0, 2));
+        game.clock.tick(0, 1.5);
+
+        let s = write_game(&game);
+        let loaded = read_game(&s).unwrap();
+
+        assert_eq!(s, write_game(&loaded));
+        assert_eq!(loaded.teams, game.teams);
+        assert_eq!(loaded.board, game.board);
+        assert_eq!(loaded.clock, game.clock);
+        assert_eq!(loaded.rules.name(), "strict");
+        assert_eq!(loaded.offers, game.offers);
+        assert_eq!(loaded.fog, Some(Vision::Radius(2)));
+    }
+
+    #[test]
+    fn old_saves_get_ids() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 3);
+        act(&mut game, (1, 0), (0, 0));
+
+        // the same game as version 1 wrote it, without ids
+        let old = write_game(&game).replace("chess_like 2", "chess_like 1").lines()
+            .map(|line| line.split(' ').filter(|t| !t.starts_with('#')).collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>().join("\n");
+        let mut loaded = read_game(&old).unwrap();
+
+        // the upgraded piece is the one placed first, pieces are numbered in the order they
+        // were placed, and the next piece gets a new id
+        let first = loaded.action_stack[0].pieces()[0].id();
+        assert_eq!(loaded.get_piece(0, 0).map(|p| p.id()), Some(first));
+        let placed: Vec<u32> = loaded.action_stack[..4].iter().map(|a| a.pieces()[0].id()).collect();
+        assert_eq!(placed, vec![1, 2, 3, 4]);
+        assert!(loaded.verify_history().is_ok());
+        assert_eq!(write_game(&loaded), write_game(&read_game(&write_game(&loaded)).unwrap()));
+        let action = loaded.place(2, 2, loaded.turn).unwrap();
+        assert!(loaded.action_stack.iter().flat_map(|a| a.pieces()).all(|p| p.id() != action.pieces()[0].id()));
+    }
+
+    #[test]
+    fn teams_and_levels_must_exist() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        let s = write_game(&game);
+
+        let err = |s: &str| read_game(s).err().unwrap_or_default();
+
+        assert!(err(&s.replace("piece #1 0", "piece #1 4")).contains("team 4"));
+        assert!(err(&s.replace("action place #1 0 0 0 1", "action place #1 0 0 0 0")).contains("level 0"));
+        assert!(err(&format!("{}action pass 2\n", s)).contains("team 2"));
+    }
+
+    #[test]
+    fn bad_header() {
+        assert!(read_game("board 1 1").is_err());
+    }
+}
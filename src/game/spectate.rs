@@ -0,0 +1,354 @@
+//! Lets other clients watch a game over TCP, without being able to act in it
+//!
+//! Lines sent by the game to every spectator:
+//!
+//! ```text
+//! chess_like-spectate 1
+//! snapshot <n>                    then the n lines of the game, in the save file format
+//! action <action>                 an action that was just played
+//! clock <spent> <remaining> ..    the time on every team's timer
+//! ```
+//!
+//! A spectator gets a snapshot when it joins, and again whenever the history is changed by
+//! anything other than a new action, like an undo or loading a save. The broadcast hears of
+//! both as an observer of the game. Whatever a spectator sends is never read.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use std::mem;
+
+use piston_window::*;
+
+use gui::{App, Data, AppGraphics};
+use game::{Game, save, HUD_FONT_SIZE};
+use game::clock::{Clock, Timer};
+use game::events::{Event, Observer};
+use game::theme::Theme;
+
+const PROTOCOL: &str = "chess_like-spectate 1";
+
+// seconds between clock updates
+const CLOCK_INTERVAL: f64 = 1.0;
+
+// height in pixels of the move list below the board
+const FOOTER_HEIGHT: u32 = 76;
+const MOVES_SHOWN: usize = 3;
+
+/// Sends a game to the spectators connected to it
+pub struct Broadcast {
+    listener: TcpListener,
+    spectators: Vec<TcpStream>,
+    // how much of the history the spectators have been sent, and the lines still to send
+    sent: usize,
+    out: String,
+    timer: f64,
+}
+
+impl Broadcast {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address)
+            .and_then(|l| l.set_nonblocking(true).map(|_| l))
+            .map_err(|e| format!("could not listen on {}: {}", address, e))?;
+
+        Ok(Self { listener, spectators: Vec::new(), sent: 0, out: String::new(), timer: 0.0 })
+    }
+
+    pub fn address(&self) -> String {
+        self.listener.local_addr().map(|a| a.to_string()).unwrap_or_default()
+    }
+
+    fn snapshot(game: &Game) -> String {
+        let position = save::write_game(game);
+
+        format!("snapshot {}\n{}", position.lines().count(), position)
+    }
+
+    fn clock(game: &Game) -> String {
+        let remaining: Vec<String> = game.clock.timers().iter().map(|t| t.remaining.to_string()).collect();
+
+        format!("clock {} {}\n", game.clock.spent(), remaining.join(" "))
+    }
+
+    /// sends the spectators what happened since the last update, `dt` seconds ago, and lets
+    /// new ones join
+    pub fn update(&mut self, game: &Game, dt: f64) {
+        let mut out = mem::take(&mut self.out);
+
+        self.timer += dt;
+        if self.timer >= CLOCK_INTERVAL || !out.is_empty() {
+            self.timer = 0.0;
+            out += &Broadcast::clock(game);
+        }
+
+        // a spectator that can't keep up is dropped
+        self.spectators.retain(|mut s| s.write_all(out.as_bytes()).is_ok());
+
+        while let Ok((mut stream, address)) = self.listener.accept() {
+            let hello = format!("{}\n{}{}", PROTOCOL, Broadcast::snapshot(game), Broadcast::clock(game));
+            let joined = stream.set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(Duration::from_millis(200))))
+                .and_then(|_| stream.write_all(hello.as_bytes()));
+
+            match joined {
+                Ok(()) => {
+                    println!("{} is spectating", address);
+                    self.spectators.push(stream);
+                },
+                Err(msg) => println!("err = {}: {}", address, msg),
+            }
+        }
+    }
+}
+
+impl Observer for Broadcast {
+    fn notify(&mut self, game: &Game, event: &Event) {
+        let history = &game.action_stack;
+
+        match *event {
+            Event::Undone(_) | Event::Loaded => {
+                self.out += &Broadcast::snapshot(game);
+                self.sent = history.len();
+            },
+            Event::Turn(_) | Event::GameOver(_) => (),
+            // an action, which is sent once however many events it makes
+            _ => {
+                for action in &history[self.sent.min(history.len())..] {
+                    self.out += &format!("action {}\n", save::write_action(action));
+                }
+                self.sent = history.len();
+            },
+        }
+    }
+}
+
+/// Watches a game sent by a `Broadcast`
+pub struct Spectator {
+    game: Option<Game>,
+    address: String,
+    lines: Receiver<String>,
+    greeted: bool,
+    connected: bool,
+    // how many lines of a snapshot are still to come, and those read so far
+    pending: Option<(usize, Vec<String>)>,
+    theme: Theme,
+    data: AtomicPtr<Data>,
+    window: AtomicPtr<PistonWindow>,
+}
+
+impl Spectator {
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("could not connect to {}: {}", address, e))?;
+        let (send, lines) = channel();
+
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                match line {
+                    Ok(line) => if send.send(line).is_err() { break },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            game: None, address: String::from(address), lines,
+            greeted: false, connected: true, pending: None, theme: Theme::classic(),
+            data: AtomicPtr::default(), window: AtomicPtr::default(),
+        })
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    fn receive(&mut self, line: String) -> Result<(), String> {
+        if !self.greeted {
+            self.greeted = true;
+            return if line == PROTOCOL { Ok(()) } else { Err(format!("{} is not a chess_like game", self.address)) };
+        }
+
+        if let Some((n, mut lines)) = self.pending.take() {
+            lines.push(line);
+            if lines.len() < n {
+                self.pending = Some((n, lines));
+            } else {
+                self.show(save::read_game(&lines.join("\n"))?);
+            }
+            return Ok(());
+        }
+
+        let mut tokens = line.split_whitespace();
+        match (tokens.next(), self.game.as_mut()) {
+            (Some("snapshot"), _) => {
+                let n = tokens.next().and_then(|n| n.parse().ok()).ok_or("bad snapshot")?;
+                self.pending = Some((n, Vec::new()));
+                Ok(())
+            },
+            (Some("action"), Some(game)) => {
+                let action = save::read_action(&mut tokens)?;
+                game.do_action(action).map_err(|e| e.to_string())
+            },
+            (Some("clock"), Some(game)) => {
+                let spent = tokens.next().and_then(|s| s.parse().ok()).ok_or("bad clock")?;
+                let timers = game.clock.timers().iter().zip(tokens)
+                    .map(|(t, remaining)| remaining.parse().map(|remaining| Timer { control: t.control, remaining }))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("bad clock: {}", e))?;
+
+                game.clock = Clock::from_timers(game.clock.policy, timers, spent);
+                Ok(())
+            },
+            _ => Err(format!("unexpected {:?}", line)),
+        }
+    }
+
+    // replaces the game on screen with a snapshot
+    fn show(&mut self, mut game: Game) {
+        // spectators see the whole board
+        game.fog = None;
+
+        match self.game {
+            Some(ref mut old) => old.restore(game),
+            None => {
+                game.footer = FOOTER_HEIGHT;
+                game.theme = self.theme.clone();
+                game.set_data(AtomicPtr::new(self.data.load(Ordering::Relaxed)));
+                if !self.window.load(Ordering::Relaxed).is_null() {
+                    game.set_window(AtomicPtr::new(self.window.load(Ordering::Relaxed)));
+                }
+                self.game = Some(game);
+            },
+        }
+    }
+
+    /// applies everything the game has sent since the last poll
+    pub fn poll(&mut self) {
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => if let Err(msg) = self.receive(line) {
+                    println!("err = {}", msg);
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.connected {
+                        println!("the game at {} has ended the connection", self.address);
+                    }
+                    self.connected = false;
+                    break;
+                },
+            }
+        }
+    }
+}
+
+impl App for Spectator {
+    fn set_data(&mut self, data: AtomicPtr<Data>) {
+        self.data = data;
+    }
+
+    fn set_window(&mut self, window: AtomicPtr<PistonWindow>) {
+        self.window = window;
+    }
+
+    fn render(&self, c: Context, g: &mut AppGraphics) {
+        let game = match self.game {
+            Some(ref game) => game,
+            None => {
+                clear(self.theme.background, g);
+                return;
+            },
+        };
+
+        game.render(c, g);
+
+        let mut y = game.footer_top() + HUD_FONT_SIZE as f64;
+        let status = if self.connected { "spectating" } else { "disconnected from" };
+
+        game.draw_text(game.theme.text, &format!("{} {}", status, self.address), 16.0, y, c, g);
+
+        let history = &game.action_stack;
+        for (i, action) in history.iter().enumerate().skip(history.len().saturating_sub(MOVES_SHOWN)) {
+            y += HUD_FONT_SIZE as f64 + 4.0;
+            game.draw_text(game.theme.text, &format!("{}. {}", i + 1, game.describe(action)), 16.0, y, c, g);
+        }
+    }
+
+    fn update(&mut self, args: &UpdateArgs) {
+        self.poll();
+
+        // the clock runs on between updates from the game
+        if let Some(ref mut game) = self.game {
+            game.animator.update(args.dt);
+            if !game.is_over() {
+                game.clock.tick(game.turn, args.dt);
+            }
+        }
+    }
+
+    fn mouse_moved(&mut self, args: &Motion) {
+        if let Some(ref mut game) = self.game {
+            game.mouse_moved(args);
+        }
+    }
+
+    fn handle_cursor(&mut self, cursor: bool) {
+        if let Some(ref mut game) = self.game {
+            game.handle_cursor(cursor);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, place};
+
+    // updates until the spectator has caught up with `game`
+    fn sync(game: &mut Game, spectator: &mut Spectator) {
+        for _ in 0..200 {
+            let mut broadcast = game.spectators.take().unwrap();
+            broadcast.update(game, 0.0);
+            game.spectators = Some(broadcast);
+            spectator.poll();
+
+            let caught_up = spectator.game.as_ref().is_some_and(|s| {
+                s.board == game.board && s.turn == game.turn && s.action_stack.len() == game.action_stack.len()
+            });
+            if caught_up && spectator.pending.is_none() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("the spectator never caught up");
+    }
+
+    #[test]
+    fn spectators_join_mid_game() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        place(&mut game, 4, 4);
+
+        let broadcast = Broadcast::bind("127.0.0.1:0").unwrap();
+        let mut spectator = Spectator::connect(&broadcast.address()).unwrap();
+        let mut game = game.with_spectators(broadcast);
+        sync(&mut game, &mut spectator);
+
+        place(&mut game, 1, 0);
+        sync(&mut game, &mut spectator);
+
+        game.undo_last();
+        game.undo_last();
+        sync(&mut game, &mut spectator);
+        assert_eq!(spectator.game.as_ref().unwrap().action_stack.len(), 1);
+
+        // a timeout reaches the spectators as an action
+        game.time_out();
+        sync(&mut game, &mut spectator);
+        assert!(spectator.game.as_ref().unwrap().teams[1].eliminated);
+    }
+}
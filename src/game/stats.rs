@@ -0,0 +1,346 @@
+//! Statistics about a game, worked out from its history
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+use piston_window::*;
+
+use gui::{AppGraphics, unwrap};
+use game::{Game, HUD_HEIGHT, HUD_FONT_SIZE};
+use game::action::*;
+use game::pieces::Piece;
+
+// the margin around the report drawn over the board
+const MARGIN: f64 = 16.0;
+const PANEL: [f32; 4] = [1.0, 1.0, 1.0, 0.92];
+const GRAPH_HEIGHT: f64 = 140.0;
+
+/// What one team did over the game
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tally {
+    pub placed: u32,
+    pub moved: u32,
+    pub captured: u32,
+    pub upgraded: u32,
+    // pieces taken by other teams or merged away
+    pub lost: u32,
+    pub passed: u32,
+}
+
+/// The piece that stayed on the board the longest, counted in plies
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lifetime {
+    pub id: u32,
+    pub team: usize,
+    pub born: usize,
+    // still on the board at the end if `None`
+    pub died: Option<usize>,
+    // where it was last
+    pub square: (i32, i32),
+}
+
+pub struct Stats {
+    pub names: Vec<String>,
+    pub colors: Vec<[f32; 4]>,
+    pub tallies: Vec<Tally>,
+    // the team and kind of each action, in order
+    pub actions: Vec<(usize, &'static str)>,
+    // for every ply, starting before the first action: the sum of the levels of each team's pieces
+    pub material: Vec<Vec<u32>>,
+    // for every ply: how many pieces of each level there are, from level 1 up
+    pub levels: Vec<Vec<u32>>,
+    pub longest: Option<Lifetime>,
+}
+
+fn kind(action: &Action) -> &'static str {
+    match *action {
+        Action::Place(_) => "place",
+        Action::Remove(_) => "remove",
+        Action::Move(..) => "move",
+        Action::Upgrade(..) => "upgrade",
+        Action::Composite(Composite::Capture(_)) => "capture",
+        Action::Pass(_) => "pass",
+        Action::Ally(_, _) => "ally",
+        Action::Break(_, _) => "break",
+        Action::Forfeit(_) => "forfeit",
+    }
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn json_list<T: ToString>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+impl Stats {
+    /// the statistics of the history of `game`, or why they can't be worked out
+    pub fn collect(game: &Game) -> Result<Self, String> {
+        let teams = game.teams.len();
+        let max_level = game.rules.max_level() as usize;
+
+        // the tallies below count by team and level
+        let mut pieces = game.board.iter().chain(game.action_stack.iter().flat_map(|a| a.pieces()));
+        if let Some(p) = pieces.find(|p| p.team() >= teams || p.level() == 0) {
+            return Err(format!("no statistics: {} has no team or no level", p));
+        }
+        if let Some(action) = game.action_stack.iter().find(|a| a.team() >= teams) {
+            return Err(format!("no statistics: {:?} belongs to team {}, which does not exist", action, action.team()));
+        }
+
+        // step back through the history from the final board, then turn the series around
+        let mut board = Game::from_vec(game.board.width(), game.board.height(),
+                                       game.board.iter().cloned().collect(), game.teams.clone());
+        let mut material = Vec::new();
+        let mut levels = Vec::new();
+        let mut snapshot = |board: &Game| {
+            let mut team_material = vec![0; teams];
+            let mut level_counts = vec![0; max_level];
+
+            for p in board.board.iter() {
+                team_material[p.team()] += p.level();
+                if let Some(count) = level_counts.get_mut(p.level() as usize - 1) {
+                    *count += 1;
+                }
+            }
+            material.push(team_material);
+            levels.push(level_counts);
+        };
+
+        snapshot(&board);
+        for (i, action) in game.action_stack.iter().enumerate().rev() {
+            board.undo_action(action.clone())
+                .map_err(|e| format!("no statistics: action {} could not be undone: {}", i + 1, e))?;
+            snapshot(&board);
+        }
+        material.reverse();
+        levels.reverse();
+
+        let mut tallies = vec![Tally::default(); teams];
+        // the team and birth ply of every piece on the board, by id
+        let mut alive: HashMap<u32, (usize, usize)> = HashMap::new();
+        let mut longest: Option<Lifetime> = None;
+        let total = game.action_stack.len();
+
+        {
+            let mut record = |lifetime: Lifetime| {
+                let length = |l: &Lifetime| l.died.unwrap_or(total) - l.born;
+                if longest.as_ref().is_none_or(|best| length(&lifetime) > length(best)) {
+                    longest = Some(lifetime);
+                }
+            };
+            let mut die = |alive: &mut HashMap<u32, (usize, usize)>, p: &Piece, ply, tallies: &mut Vec<Tally>| {
+                let (team, born) = alive.remove(&p.id()).unwrap_or((p.team(), 0));
+                tallies[team].lost += 1;
+                record(Lifetime { id: p.id(), team, born, died: Some(ply), square: (p.x(), p.y()) });
+            };
+
+            for (i, action) in game.action_stack.iter().enumerate() {
+                let team = action.team();
+                let ply = i + 1;
+
+                match *action {
+                    Action::Place(ref p) => {
+                        tallies[team].placed += 1;
+                        alive.insert(p.id(), (p.team(), ply));
+                    },
+                    Action::Remove(ref p) => die(&mut alive, p, ply, &mut tallies),
+                    Action::Move(..) => tallies[team].moved += 1,
+                    Action::Upgrade(ref sacrifice, _, _) => {
+                        tallies[team].upgraded += 1;
+                        die(&mut alive, sacrifice, ply, &mut tallies);
+                    },
+                    Action::Composite(Composite::Capture(ref c)) => {
+                        tallies[team].captured += 1;
+                        tallies[team].moved += 1;
+                        if let Action::Remove(ref victim) = c.remove_action {
+                            die(&mut alive, victim, ply, &mut tallies);
+                        }
+                    },
+                    Action::Pass(_) => tallies[team].passed += 1,
+                    Action::Ally(_, _) | Action::Break(_, _) | Action::Forfeit(_) => (),
+                }
+            }
+
+            // pieces on the board from the start were born at ply 0
+            for p in game.board.iter() {
+                let (team, born) = alive.get(&p.id()).cloned().unwrap_or((p.team(), 0));
+                record(Lifetime { id: p.id(), team, born, died: None, square: (p.x(), p.y()) });
+            }
+        }
+
+        Ok(Self {
+            names: game.teams.iter().map(|t| t.name.clone()).collect(),
+            colors: (0..game.teams.len()).map(|t| game.team_color(t)).collect(),
+            tallies,
+            actions: game.action_stack.iter().map(|a| (a.team(), kind(a))).collect(),
+            material, levels, longest,
+        })
+    }
+
+    /// a few lines to read at the end of a game
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.names.iter().zip(&self.tallies).map(|(name, t)| {
+            format!("{}: {} placed, {} moves, {} captures, {} upgrades, {} lost",
+                    name, t.placed, t.moved, t.captured, t.upgraded, t.lost)
+        }).collect();
+
+        if let Some(ref l) = self.longest {
+            let end = l.died.map(|d| format!("until ply {}", d)).unwrap_or_else(|| String::from("to the end"));
+            lines.push(format!("longest lived: piece #{} of {} from ply {} {}, last at ({}, {})",
+                               l.id, self.names[l.team], l.born, end, l.square.0, l.square.1));
+        }
+
+        lines
+    }
+
+    /// one row per ply, with the action that led to it
+    pub fn to_csv(&self) -> String {
+        let mut header = vec![String::from("ply"), String::from("team"), String::from("action")];
+        header.extend(self.names.iter().map(|n| format!("\"material {}\"", n.replace('"', "\"\""))));
+        header.extend((1..=self.levels[0].len()).map(|l| format!("level {}", l)));
+
+        let mut out = header.join(",") + "\n";
+        for (ply, (material, levels)) in self.material.iter().zip(&self.levels).enumerate() {
+            let (team, action) = match ply {
+                0 => (String::new(), ""),
+                _ => (self.actions[ply - 1].0.to_string(), self.actions[ply - 1].1),
+            };
+            let mut row = vec![ply.to_string(), team, String::from(action)];
+            row.extend(material.iter().chain(levels).map(|v| v.to_string()));
+
+            out += &(row.join(",") + "\n");
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let teams: Vec<String> = self.names.iter().zip(&self.tallies).map(|(name, t)| {
+            format!("    {{\"name\": {}, \"placed\": {}, \"moved\": {}, \"captured\": {}, \"upgraded\": {}, \"lost\": {}, \"passed\": {}}}",
+                    json_string(name), t.placed, t.moved, t.captured, t.upgraded, t.lost, t.passed)
+        }).collect();
+        let actions: Vec<String> = self.actions.iter()
+            .map(|&(team, kind)| format!("{{\"team\": {}, \"action\": {}}}", team, json_string(kind)))
+            .collect();
+        let series = |rows: &[Vec<u32>]| rows.iter().map(|r| json_list(r)).collect::<Vec<_>>().join(", ");
+        let longest = match self.longest {
+            Some(ref l) => format!("{{\"id\": {}, \"team\": {}, \"born\": {}, \"died\": {}, \"x\": {}, \"y\": {}}}",
+                                   l.id, l.team, l.born, l.died.map(|d| d.to_string()).unwrap_or_else(|| String::from("null")),
+                                   l.square.0, l.square.1),
+            None => String::from("null"),
+        };
+
+        format!("{{\n  \"teams\": [\n{}\n  ],\n  \"actions\": [{}],\n  \"material\": [{}],\n  \"levels\": [{}],\n  \"longest_lived\": {}\n}}\n",
+                teams.join(",\n"), actions.join(", "), series(&self.material), series(&self.levels), longest)
+    }
+
+    /// writes a `.csv` or `.json` report, going by the extension of `path`
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let report = if path.ends_with(".csv") {
+            self.to_csv()
+        } else if path.ends_with(".json") {
+            self.to_json()
+        } else {
+            return Err(format!("{} should end in .csv or .json", path));
+        };
+
+        File::create(path)
+            .and_then(|mut file| file.write_all(report.as_bytes()))
+            .map_err(|e| format!("could not write {}: {}", path, e))
+    }
+}
+
+impl Game {
+    // the report over the board at the end of the game, with a graph of each team's material
+    pub fn render_stats(&self, stats: &Result<Stats, String>, c: Context, g: &mut AppGraphics) {
+        use graphics::*;
+
+        let data = unwrap(&self.data);
+        let (width, height) = (data.screen_width as f64, self.footer_top());
+        let top = HUD_HEIGHT as f64 + MARGIN;
+        rectangle(PANEL, [MARGIN, top, width - 2.0 * MARGIN, height - top - MARGIN], c.transform, g);
+
+        let mut y = top;
+        let stats = match *stats {
+            Ok(ref stats) => stats,
+            Err(ref msg) => {
+                self.draw_text(self.theme.text, msg, 2.0 * MARGIN, y + HUD_FONT_SIZE as f64 + 6.0, c, g);
+                return;
+            },
+        };
+        for line in stats.summary() {
+            y += HUD_FONT_SIZE as f64 + 6.0;
+            self.draw_text(self.theme.text, &line, 2.0 * MARGIN, y, c, g);
+        }
+
+        let (left, right) = (2.0 * MARGIN, width - 2.0 * MARGIN);
+        let bottom = (y + MARGIN + GRAPH_HEIGHT).min(height - 2.0 * MARGIN);
+        let most = stats.material.iter().flat_map(|m| m.iter()).cloned().max().unwrap_or(0).max(1) as f64;
+        let plies = (stats.material.len() - 1).max(1) as f64;
+        let point = |ply: usize, value: u32| {
+            [left + (right - left) * ply as f64 / plies, bottom - (bottom - y - MARGIN) * value as f64 / most]
+        };
+
+        line(self.theme.text, 0.5, [left, bottom, right, bottom], c.transform, g);
+        for (team, &color) in stats.colors.iter().enumerate() {
+            for (ply, pair) in stats.material.windows(2).enumerate() {
+                let (a, b) = (point(ply, pair[0][team]), point(ply + 1, pair[1][team]));
+                line(color, 1.0, [a[0], a[1], b[0], b[1]], c.transform, g);
+            }
+        }
+        self.draw_text(self.theme.text, "material by ply, T hides this", left, bottom + HUD_FONT_SIZE as f64 + 4.0, c, g);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use game::test::{two_teams, act, place};
+
+    #[test]
+    fn counts_the_history() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+        place(&mut game, 0, 2);
+        place(&mut game, 1, 0);
+        place(&mut game, 4, 4);
+        act(&mut game, (1, 0), (0, 0));
+        act(&mut game, (0, 2), (0, 1));
+        act(&mut game, (0, 0), (0, 1));
+
+        let stats = Stats::collect(&game).unwrap();
+        assert_eq!(stats.tallies[0], Tally { placed: 2, moved: 1, captured: 1, upgraded: 1, lost: 1, passed: 0 });
+        assert_eq!(stats.tallies[1], Tally { placed: 2, moved: 1, captured: 0, upgraded: 0, lost: 1, passed: 0 });
+
+        assert_eq!(stats.material.len(), 8);
+        assert_eq!(stats.material[0], vec![0, 0]);
+        assert_eq!(stats.material[4], vec![2, 2]);
+        assert_eq!(stats.material[7], vec![2, 1]);
+        assert_eq!(stats.levels[7][..2], [1, 1]);
+
+        // the piece placed first was merged into, and so lasted from ply 1 to the end
+        assert_eq!(stats.longest, Some(Lifetime { id: 1, team: 0, born: 1, died: None, square: (0, 1) }));
+
+        assert_eq!(stats.to_csv().lines().count(), 9);
+        assert!(stats.to_csv().starts_with("ply,team,action,\"material A\",\"material B\",level 1"));
+        assert!(stats.to_json().contains("\"name\": \"A\", \"placed\": 2"));
+    }
+
+    #[test]
+    fn broken_histories_have_no_stats() {
+        let mut game = two_teams();
+        place(&mut game, 0, 0);
+
+        game.action_stack.push(Action::Pass(5));
+        assert!(Stats::collect(&game).err().unwrap_or_default().contains("team 5"));
+
+        // a place of a piece that is not on the board can't be stepped back through
+        game.action_stack.pop();
+        game.action_stack.push(Action::Place(Piece::new(0, 0, 1).with_id(9)));
+        assert!(Stats::collect(&game).err().unwrap_or_default().contains("action 2"));
+    }
+}
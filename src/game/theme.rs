@@ -0,0 +1,234 @@
+//! The colours the game is drawn with, and the shape of each team's pieces
+//!
+//! Besides the presets, a theme can be loaded from a file of lines that each change one
+//! thing about the classic theme:
+//!
+//! ```text
+//! background <r> <g> <b> <a>
+//! text <r> <g> <b> <a>
+//! dark <r> <g> <b> <a>             the dark squares of the board
+//! light <r> <g> <b> <a>            the light squares, tinted by the team to move if missing
+//! selected|place|fog|wall <r> <g> <b> <a>
+//! move|upgrade|capture <r> <g> <b> <a>
+//! team <r> <g> <b> <a>             the colour of the next team, replacing the saved one
+//! markers <marker> ..              the shape of each team's pieces, in team order
+//! ```
+//!
+//! a marker is `circle`, `square`, `triangle`, `diamond` or `hexagon`.
+
+use std::fs::File;
+use std::io::Read;
+
+use game::Reach;
+
+pub const THEMES: [&str; 3] = ["classic", "colorblind", "high-contrast"];
+
+const MARKERS: [Marker; 5] = [Marker::Circle, Marker::Square, Marker::Triangle, Marker::Diamond, Marker::Hexagon];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Marker {
+    Circle,
+    Square,
+    Triangle,
+    Diamond,
+    Hexagon,
+}
+
+impl Marker {
+    pub fn parse(s: &str) -> Result<Marker, String> {
+        match s {
+            "circle" => Ok(Marker::Circle),
+            "square" => Ok(Marker::Square),
+            "triangle" => Ok(Marker::Triangle),
+            "diamond" => Ok(Marker::Diamond),
+            "hexagon" => Ok(Marker::Hexagon),
+            _ => Err(format!("unknown marker `{}`", s)),
+        }
+    }
+
+    /// the corners of the marker drawn inside `rect`, none for a circle
+    pub fn corners(self, rect: [f64; 4]) -> Option<Vec<[f64; 2]>> {
+        let [x, y, w, h] = rect;
+        let at = |u: f64, v: f64| [x + u * w, y + v * h];
+
+        match self {
+            Marker::Circle => None,
+            Marker::Square => Some(vec![at(0.1, 0.1), at(0.9, 0.1), at(0.9, 0.9), at(0.1, 0.9)]),
+            Marker::Triangle => Some(vec![at(0.5, 0.0), at(1.0, 0.9), at(0.0, 0.9)]),
+            Marker::Diamond => Some(vec![at(0.5, 0.0), at(1.0, 0.5), at(0.5, 1.0), at(0.0, 0.5)]),
+            Marker::Hexagon => Some(vec![at(0.25, 0.05), at(0.75, 0.05), at(1.0, 0.5), at(0.75, 0.95), at(0.25, 0.95), at(0.0, 0.5)]),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub background: [f32; 4],
+    pub text: [f32; 4],
+    pub dark: [f32; 4],
+    pub light: Option<[f32; 4]>,
+    pub selected: [f32; 4],
+    pub place_zone: [f32; 4],
+    pub fog: [f32; 4],
+    pub wall: [f32; 4],
+    pub moves: [f32; 4],
+    pub upgrade: [f32; 4],
+    pub capture: [f32; 4],
+    // replace the colours of the first teams, the others keep their own
+    pub teams: Vec<[f32; 4]>,
+    pub markers: Vec<Marker>,
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Self {
+            background: [1.0, 1.0, 1.0, 1.0],
+            text: [0.0, 0.0, 0.0, 1.0],
+            dark: [0.0, 0.0, 0.0, 1.0],
+            light: None,
+            selected: [0.7, 0.7, 0.7, 1.0],
+            place_zone: [0.5, 0.5, 0.5, 1.0],
+            fog: [0.25, 0.25, 0.25, 1.0],
+            wall: [0.35, 0.25, 0.15, 1.0],
+            moves: [0.4, 0.3, 1.0, 1.0],
+            upgrade: [0.3, 1.0, 0.4, 1.0],
+            capture: [1.0, 0.4, 0.3, 1.0],
+            teams: Vec::new(),
+            markers: vec![Marker::Circle],
+        }
+    }
+
+    /// the Okabe-Ito colours, which stay apart under every common kind of colour blindness
+    pub fn colorblind() -> Self {
+        Self {
+            moves: [0.34, 0.71, 0.91, 1.0],
+            upgrade: [0.94, 0.89, 0.26, 1.0],
+            capture: [0.84, 0.37, 0.0, 1.0],
+            teams: vec![[0.9, 0.62, 0.0, 1.0], [0.0, 0.45, 0.7, 1.0], [0.0, 0.62, 0.45, 1.0], [0.8, 0.47, 0.65, 1.0]],
+            markers: MARKERS.to_vec(),
+            ..Theme::classic()
+        }
+    }
+
+    /// black and white squares, with strong colours and a different shape for every team
+    pub fn high_contrast() -> Self {
+        Self {
+            light: Some([1.0, 1.0, 1.0, 1.0]),
+            selected: [0.55, 0.55, 0.55, 1.0],
+            place_zone: [0.8, 0.8, 0.8, 1.0],
+            fog: [0.4, 0.4, 0.4, 1.0],
+            moves: [0.0, 0.3, 1.0, 1.0],
+            upgrade: [0.0, 0.8, 0.0, 1.0],
+            capture: [1.0, 0.0, 0.0, 1.0],
+            teams: vec![[0.85, 0.0, 0.85, 1.0], [1.0, 0.55, 0.0, 1.0], [0.0, 0.6, 0.9, 1.0], [0.6, 0.3, 0.0, 1.0]],
+            markers: MARKERS.to_vec(),
+            ..Theme::classic()
+        }
+    }
+
+    /// a preset, or else the theme file at `name`
+    pub fn by_name(name: &str) -> Result<Self, String> {
+        match name {
+            "classic" => Ok(Theme::classic()),
+            "colorblind" => Ok(Theme::colorblind()),
+            "high-contrast" => Ok(Theme::high_contrast()),
+            _ => Theme::load(name).map_err(|e| format!("{}, themes are {} or a theme file", e, THEMES.join(", "))),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut theme = Theme::classic();
+
+        for (number, line) in s.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+            let err = |e: String| format!("line {}: {}", number + 1, e);
+
+            if keyword == "markers" {
+                theme.markers = tokens.map(Marker::parse).collect::<Result<_, _>>().map_err(err)?;
+                continue;
+            }
+
+            let color = read_color(tokens).map_err(err)?;
+            match keyword {
+                "background" => theme.background = color,
+                "text" => theme.text = color,
+                "dark" => theme.dark = color,
+                "light" => theme.light = Some(color),
+                "selected" => theme.selected = color,
+                "place" => theme.place_zone = color,
+                "fog" => theme.fog = color,
+                "wall" => theme.wall = color,
+                "move" => theme.moves = color,
+                "upgrade" => theme.upgrade = color,
+                "capture" => theme.capture = color,
+                "team" => theme.teams.push(color),
+                _ => return Err(err(format!("unknown setting `{}`", keyword))),
+            }
+        }
+
+        Ok(theme)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut s = String::new();
+
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut s))
+            .map_err(|e| format!("could not load {}: {}", path, e))?;
+
+        Theme::parse(&s)
+    }
+
+    pub fn reach_color(&self, reach: Reach) -> [f32; 4] {
+        match reach {
+            Reach::Move => self.moves,
+            Reach::Upgrade => self.upgrade,
+            Reach::Capture => self.capture,
+        }
+    }
+
+    pub fn marker(&self, team: usize) -> Marker {
+        self.markers.get(team).cloned().unwrap_or(Marker::Circle)
+    }
+}
+
+fn read_color<'a, I>(tokens: I) -> Result<[f32; 4], String>
+    where I: Iterator<Item = &'a str> {
+    let values = tokens.map(|t| t.parse::<f32>().map_err(|_| format!("invalid value `{}`", t)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [r, g, b, a] => Ok([r, g, b, a]),
+        _ => Err(String::from("a colour is four numbers, r g b a")),
+    }
+}
+
+// a lighter version of `color`, for the squares tinted by the team to move
+pub fn lighten(color: [f32; 4]) -> [f32; 4] {
+    let l = |v: f32| (v * 4.0 / 3.0).min(1.0);
+
+    [l(color[0]), l(color[1]), l(color[2]), color[3]]
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn files_change_the_classic_theme() {
+        let theme = Theme::parse("text 1 1 1 1\nteam 0 0 1 1\n\nmarkers square triangle\n").unwrap();
+
+        assert_eq!(theme.text, [1.0; 4]);
+        assert_eq!(theme.teams, vec![[0.0, 0.0, 1.0, 1.0]]);
+        assert_eq!((theme.marker(1), theme.marker(2)), (Marker::Triangle, Marker::Circle));
+        assert_eq!(theme.background, Theme::classic().background);
+
+        assert!(Theme::parse("wall 1 1").unwrap_err().contains("line 1"));
+        assert!(Theme::parse("markers star").is_err());
+        assert!(Theme::by_name("no-such-theme").unwrap_err().contains("high-contrast"));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt::{Display, Formatter, Result};
+
+use game::board::Board;
+
+/// An area of the board where a team is allowed to place new pieces
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlacementZone {
+    /// every row in `start..end`
+    Rows(u32, u32),
+    /// the rectangle at `(x, y)` with the given `(width, height)`
+    Region(u32, u32, u32, u32),
+    /// any square orthogonally next to one of the team's own pieces
+    AdjacentToOwn,
+}
+
+impl PlacementZone {
+    pub fn contains(&self, x: u32, y: u32, team: usize, board: &Board) -> bool {
+        match *self {
+            PlacementZone::Rows(start, end) => start <= y && y < end,
+            PlacementZone::Region(rx, ry, w, h) =>
+                rx <= x && x < rx + w && ry <= y && y < ry + h,
+            PlacementZone::AdjacentToOwn => {
+                let (x, y) = (x as i32, y as i32);
+
+                [(1, 0), (-1, 0), (0, 1), (0, -1)].iter()
+                    .filter_map(|&(dx, dy)| board.get(x + dx, y + dy))
+                    .any(|p| p.team() == team)
+            }
+        }
+    }
+}
+
+impl Display for PlacementZone {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            PlacementZone::Rows(start, end) => write!(f, "rows {}..{}", start, end),
+            PlacementZone::Region(x, y, w, h) => write!(f, "region ({}, {}) {}x{}", x, y, w, h),
+            PlacementZone::AdjacentToOwn => write!(f, "adjacent to own pieces"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::PlacementZone;
+    use game::board::Board;
+    use game::pieces::Piece;
+
+    #[test]
+    fn adjacent_to_own() {
+        let mut board = Board::new(8, 8);
+        board.insert(Piece::new(3, 3, 0)).unwrap();
+        board.insert(Piece::new(5, 5, 1)).unwrap();
+        let zone = PlacementZone::AdjacentToOwn;
+
+        assert!(zone.contains(3, 4, 0, &board));
+        assert!(!zone.contains(4, 4, 0, &board));
+        assert!(!zone.contains(5, 4, 0, &board));
+        assert!(zone.contains(5, 4, 1, &board));
+    }
+
+    #[test]
+    fn region() {
+        let zone = PlacementZone::Region(2, 2, 2, 3);
+        let board = Board::new(8, 8);
+
+        assert!(zone.contains(2, 2, 0, &board));
+        assert!(zone.contains(3, 4, 0, &board));
+        assert!(!zone.contains(4, 4, 0, &board));
+        assert!(!zone.contains(3, 5, 0, &board));
+    }
+}
@@ -50,6 +50,9 @@ pub struct Data {
     pub is_window_focus: bool,
     pub screen_width: u32,
     pub screen_height: u32,
+    // screen pixels per window unit, above 1 on HiDPI screens
+    pub scale: f64,
+    pub fullscreen: bool,
     pub mouse_x: f64,
     pub mouse_y: f64,
     pub button_held: Vec<Button>
@@ -64,6 +67,8 @@ impl Data {
             is_cursor_on: false,
             is_window_focus: false,
             screen_width, screen_height,
+            scale: window.window.window.hidpi_factor() as f64,
+            fullscreen: false,
             mouse_x: 0.0,
             mouse_y: 0.0,
             button_held: Vec::new()
@@ -99,7 +104,7 @@ pub fn start<T>(mut window: PistonWindow, mut app: T)
     loop {
         let e = unwrap_mut(&mut window).next();
 
-        if let None = e {
+        if e.is_none() {
             break;
         }
 
@@ -118,12 +123,12 @@ pub fn start<T>(mut window: PistonWindow, mut app: T)
                         let d = unwrap(&data);
 
                         for button in &d.button_held {
-                            match button {
-                                &Button::Keyboard(key) => 
+                            match *button {
+                                Button::Keyboard(key) => 
                                     app.handle_key_held(key),
-                                &Button::Mouse(mouse_button) => 
+                                Button::Mouse(mouse_button) => 
                                     app.handle_mouse_held(mouse_button),
-                                &Button::Controller(controller_button) => 
+                                Button::Controller(controller_button) => 
                                     app.handle_controller_held(controller_button)
                             }
                         }
@@ -145,6 +150,14 @@ pub fn start<T>(mut window: PistonWindow, mut app: T)
                         let d = unwrap_mut(&mut data);
                         let contains = d.button_held.contains(&b.button);
                         
+                        // F11 switches fullscreen for every app
+                        if b.state == ButtonState::Press && b.button == Button::Keyboard(Key::F11) {
+                            let glutin = &unwrap(&window).window.window;
+
+                            d.fullscreen = !d.fullscreen;
+                            glutin.set_fullscreen(if d.fullscreen { Some(glutin.get_current_monitor()) } else { None });
+                        }
+
                         if !contains {
                             match b.button {
                                 Button::Keyboard(key) => 
@@ -176,12 +189,15 @@ pub fn start<T>(mut window: PistonWindow, mut app: T)
                             d.mouse_x = x;
                             d.mouse_y = y;
                         }
+                        app.mouse_moved(&m);
                     },
                     Input::Resize(w, h) => {
                         let d = unwrap_mut(&mut data);
 
                         d.screen_width = w;
                         d.screen_height = h;
+                        d.scale = unwrap(&window).window.window.hidpi_factor() as f64;
+                        app.handle_resize(w, h);
                     },
                     Input::Text(_t) => {
 
@@ -7,6 +7,12 @@ extern crate gfx_graphics;
 extern crate gfx_device_gl;
 extern crate find_folder;
 extern crate rand;
+extern crate png;
+
+use std::env;
+use std::time::Duration;
+
+use rand::{Rng, thread_rng};
 
 use piston::window::WindowSettings;
 use opengl_graphics::OpenGL;
@@ -14,24 +20,156 @@ use piston_window::PistonWindow;
 
 mod gui;
 mod game;
+mod bench;
+mod tournament;
 
 use game::Game;
+use game::replay::Replay;
+use game::spectate::{Broadcast, Spectator};
+use game::puzzle::Puzzle;
+use game::editor::Editor;
+use game::theme::Theme;
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let theme = match take_theme(&mut args) {
+        Ok(theme) => theme,
+        Err(msg) => {
+            println!("err = {}", msg);
+            return;
+        },
+    };
+
+    match args.first().map(|s| s.as_str()) {
+        Some("bench") => {
+            let iterations = args.get(1).and_then(|n| n.parse().ok()).unwrap_or(10_000);
+            bench::run(iterations);
+        },
+        Some("replay") => match args.get(1) {
+            Some(path) => match Replay::load(path) {
+                Ok(replay) => gui::start(window([600, 708]), replay.with_theme(theme)),
+                Err(msg) => println!("err = {}", msg),
+            },
+            None => println!("usage: chess_like replay <save file>"),
+        },
+        Some("puzzle") => match args.get(1) {
+            Some(path) => match Puzzle::load(path) {
+                Ok(puzzle) => gui::start(window([600, 708]), puzzle.with_theme(theme)),
+                Err(msg) => println!("err = {}", msg),
+            },
+            None => println!("usage: chess_like puzzle <puzzle file>"),
+        },
+        Some("edit") => match Editor::open(args.get(1).map(|s| s.as_str()).unwrap_or("scenario.save")) {
+            Ok(editor) => gui::start(window([600, 782]), editor.with_theme(theme)),
+            Err(msg) => println!("err = {}", msg),
+        },
+        Some("export") => match (args.get(1), args.get(2)) {
+            (Some(save), Some(out)) => {
+                let tile = args.get(3).and_then(|n| n.parse().ok()).unwrap_or(60.0);
+                let result = game::save::load_from_file(save)
+                    .and_then(|game| game::export::export(&game.with_theme(theme), out, tile));
+
+                if let Err(msg) = result {
+                    println!("err = {}", msg);
+                }
+            },
+            _ => println!("usage: chess_like export <save file> <out.svg|out.png> [tile pixels]"),
+        },
+        Some("stats") => match args.get(1) {
+            Some(save) => match game::save::load_from_file(save).and_then(|game| game::stats::Stats::collect(&game)) {
+                Ok(stats) => {
+                    for line in stats.summary() {
+                        println!("{}", line);
+                    }
+                    if let Some(Err(msg)) = args.get(2).map(|out| stats.write(out)) {
+                        println!("err = {}", msg);
+                    }
+                },
+                Err(msg) => println!("err = {}", msg),
+            },
+            None => println!("usage: chess_like stats <save file> [out.csv|out.json]"),
+        },
+        Some("tournament") => match tournament::Config::from_args(&args[1..]) {
+            Ok(config) => print!("{}", tournament::run(&config).report()),
+            Err(msg) => println!("err = {}", msg),
+        },
+        Some("spectate") => match args.get(1).map(|address| Spectator::connect(address)) {
+            Some(Ok(spectator)) => gui::start(window([600, 728]), spectator.with_theme(theme)),
+            Some(Err(msg)) => println!("err = {}", msg),
+            None => println!("usage: chess_like spectate <address:port>"),
+        },
+        Some("play") => match setup(&args[1..]) {
+            Ok(game) => gui::start(window([600, 652]), game.with_theme(theme)),
+            Err(msg) => println!("err = {}", msg),
+        },
+        _ => gui::start(window([600, 652]), Game::new(10, 10).with_theme(theme)),
+    }
+}
+
+const USAGE: &str = "usage: chess_like play [--scenario <file>] [--rules <name>] [--fog <reach|radius>] [--spectators <address:port>] [--events <file|->] [--seat <team> <player>]...";
+
+// `--scenario <file>` starts from a position made with `chess_like edit`,
+// `--seat <team> <player>` hands a team to a computer player, e.g. `--seat 2 engine:./bot`,
+// `--rules <name>` picks the variant to play, `--fog` hides what a team can't see and
+// `--spectators <address:port>` lets `chess_like spectate` watch from elsewhere and
+// `--events <file|->` writes down everything that happens, to the terminal for `-`
+fn setup(args: &[String]) -> Result<Game, String> {
+    let scenario = args.iter().position(|a| a == "--scenario").map(|i| args.get(i + 1).ok_or(USAGE));
+    let mut game = match scenario {
+        Some(path) => game::save::load_from_file(path?)?,
+        None => Game::new(10, 10),
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--scenario", Some(_)) => (),
+            ("--rules", Some(name)) => game = game.with_rules(game::rules::by_name(name)?),
+            ("--fog", Some(vision)) => game = game.with_fog(game::fog::Vision::parse(vision)?),
+            ("--spectators", Some(address)) => {
+                let broadcast = Broadcast::bind(address)?;
+                println!("spectators can join at {}", broadcast.address());
+                game = game.with_spectators(broadcast);
+            },
+            ("--events", Some(path)) => game = game.with_observer(Box::new(game::events::Log::open(path)?)),
+            ("--seat", Some(team)) => match (team.parse::<usize>(), args.next()) {
+                (Ok(team), Some(name)) if team >= 1 && team <= game.teams().len() => {
+                    let player = game::player::by_name(name, thread_rng().gen(), Duration::from_secs(5))?;
+                    game = game.with_bot(team - 1, player);
+                },
+                _ => return Err(String::from(USAGE)),
+            },
+            _ => return Err(String::from(USAGE)),
+        }
+    }
+
+    Ok(game)
+}
+
+// `--theme <name|file>` goes with any command that draws the board, so it is taken out
+// of the arguments before they are read
+fn take_theme(args: &mut Vec<String>) -> Result<Theme, String> {
+    match args.iter().position(|a| a == "--theme") {
+        Some(i) if i + 1 < args.len() => Theme::by_name(&args.drain(i..i + 2).nth(1).unwrap_or_default()),
+        Some(_) => Err(String::from("usage: chess_like <command> --theme <name|file>")),
+        None => Ok(Theme::classic()),
+    }
+}
+
+fn window(size: [u32; 2]) -> PistonWindow {
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V4_5;
 
     // Create an Glutin window.
-    let window: PistonWindow = WindowSettings::new(
+    WindowSettings::new(
             "chess-like",
-            [600, 600]
+            size
         )
-        .resizable(false)
+        .resizable(true)
         .samples(8)
         .opengl(opengl)
         .exit_on_esc(true)
         .build()
-        .unwrap();
-    
-    gui::start(window, Game::new(10, 10));
+        .unwrap()
 }
@@ -0,0 +1,342 @@
+//! `chess_like tournament` plays computer players against each other and reports how they did
+//!
+//! Seeds fix every choice the players make and every upgrade they roll, so two runs with the
+//! same seed play the same games.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use game::Game;
+use game::pieces::Alliance;
+use game::zone::PlacementZone;
+use game::player::{self, Player};
+use game::rules;
+use game::fog::Vision;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    RoundRobin,
+    Swiss(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub players: Vec<String>,
+    pub format: Format,
+    // games per pairing, with the seats swapped every game
+    pub games: u32,
+    pub seed: u64,
+    pub size: u32,
+    pub pieces: u32,
+    // a game that runs this long is a draw
+    pub max_moves: usize,
+    // how long an engine may think about each move
+    pub timeout: Duration,
+    pub rules: String,
+    pub fog: Option<Vision>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            players: vec![String::from("random"), String::from("greedy")],
+            format: Format::RoundRobin,
+            games: 10, seed: 0,
+            size: 6, pieces: 8,
+            max_moves: 400,
+            timeout: Duration::from_secs(5),
+            rules: String::from("standard"),
+            fog: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Config::default();
+        let mut players = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next()
+                .and_then(|v| v.parse::<u64>().ok())
+                .ok_or_else(|| format!("{} needs a number", name));
+
+            match arg.as_str() {
+                "--swiss" => config.format = Format::Swiss(value("--swiss")? as u32),
+                "--games" => config.games = value("--games")? as u32,
+                "--seed" => config.seed = value("--seed")?,
+                "--size" => config.size = value("--size")? as u32,
+                "--pieces" => config.pieces = value("--pieces")? as u32,
+                "--max-moves" => config.max_moves = value("--max-moves")? as usize,
+                "--timeout" => config.timeout = Duration::from_millis(value("--timeout")?),
+                "--fog" => config.fog = Some(Vision::parse(args.next().ok_or("--fog needs a vision")?)?),
+                "--rules" => {
+                    let name = args.next().ok_or("--rules needs a name")?;
+                    rules::by_name(name)?;
+                    config.rules = name.clone();
+                },
+                name if player::is_player(name) => players.push(String::from(name)),
+                other => return Err(format!("unknown player or option {}, players are {} or {}<command>",
+                                            other, player::PLAYERS.join(", "), player::ENGINE)),
+            }
+        }
+
+        if !players.is_empty() {
+            config.players = players;
+        }
+        if config.players.len() < 2 {
+            return Err(String::from("a tournament needs at least two players"));
+        }
+        if config.size < 4 {
+            return Err(String::from("the board needs to be at least 4 squares wide"));
+        }
+
+        Ok(config)
+    }
+
+    fn new_game(&self, seed: u64) -> Game {
+        let size = self.size;
+        let teams = vec![
+            Alliance::new("Seat 1", self.pieces, [1.0; 4]).with_zones(vec![PlacementZone::Rows(0, 2)]),
+            Alliance::new("Seat 2", self.pieces, [1.0; 4]).with_zones(vec![PlacementZone::Rows(size - 2, size)]),
+        ];
+
+        // the name was checked when the config was parsed
+        let rules = rules::by_name(&self.rules).unwrap_or_else(|_| Box::new(rules::Standard));
+
+        let game = Game::from_vec(size, size, Vec::new(), teams).with_rules(rules).with_seed(seed);
+        match self.fog {
+            Some(vision) => game.with_fog(vision),
+            None => game,
+        }
+    }
+}
+
+/// How one game ended, by seat
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    // the player sitting in each seat
+    pub seats: [usize; 2],
+    pub winner: Option<usize>,
+    pub moves: usize,
+}
+
+// a team is beaten once it has nothing on the board and nothing left to place
+fn defeated(game: &Game, team: usize) -> bool {
+    game.teams()[team].pieces_left == 0 && game.board().iter().all(|p| p.team() != team)
+}
+
+fn play_game(config: &Config, players: &mut [Box<dyn Player>; 2], seed: u64) -> (Option<usize>, usize) {
+    let mut game = config.new_game(seed);
+
+    for moves in 0..config.max_moves {
+        if let Some(loser) = (0..2).find(|&team| defeated(&game, team)) {
+            return (Some(1 - loser), moves);
+        }
+
+        let turn = game.turn();
+        let played = game.ask(&mut *players[turn])
+            .and_then(|action| game.do_action(action).map_err(|e| format!("illegal action: {}", e)));
+
+        // a player that fails or breaks the rules forfeits
+        if let Err(msg) = played {
+            println!("err = {}: {}", players[turn].name(), msg);
+            return (Some(1 - turn), moves);
+        }
+    }
+
+    (None, config.max_moves)
+}
+
+/// Results of a whole tournament
+pub struct Standings {
+    pub names: Vec<String>,
+    pub results: Vec<GameResult>,
+    pub byes: Vec<usize>,
+}
+
+impl Standings {
+    // wins, draws and losses of `player`
+    pub fn record(&self, player: usize) -> (u32, u32, u32) {
+        let mut record = (0, 0, 0);
+
+        for r in self.results.iter().filter(|r| r.seats.contains(&player)) {
+            match r.winner {
+                Some(seat) if r.seats[seat] == player => record.0 += 1,
+                Some(_) => record.2 += 1,
+                None => record.1 += 1,
+            }
+        }
+
+        record
+    }
+
+    // a win is worth 2 points, a draw 1 and a bye 2
+    pub fn points(&self, player: usize) -> u32 {
+        let (wins, draws, _) = self.record(player);
+        2 * wins + draws + 2 * self.byes.iter().filter(|&&p| p == player).count() as u32
+    }
+
+    pub fn report(&self) -> String {
+        let width = self.names.iter().map(|n| n.len()).max().unwrap_or(0).max(10);
+        let mut out = format!("{:<4} {:<width$} {:>6} {:>6} {:>6} {:>7}\n", "#", "player", "won", "drawn", "lost", "points", width = width);
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by_key(|&p| ::std::cmp::Reverse(self.points(p)));
+
+        for (rank, &p) in order.iter().enumerate() {
+            let (w, d, l) = self.record(p);
+            out += &format!("{:<4} {:<width$} {:>6} {:>6} {:>6} {:>7.1}\n",
+                            rank + 1, self.names[p], w, d, l, self.points(p) as f64 / 2.0, width = width);
+        }
+
+        let games = self.results.len().max(1) as f64;
+        let length = self.results.iter().map(|r| r.moves).sum::<usize>() as f64 / games;
+        let draws = self.results.iter().filter(|r| r.winner.is_none()).count();
+
+        out += &format!("\n{} games, {:.1} moves on average, {} drawn\n", self.results.len(), length, draws);
+        for seat in 0..2 {
+            let wins = self.results.iter().filter(|r| r.winner == Some(seat)).count();
+            out += &format!("seat {} won {} ({:.1}%)\n", seat + 1, wins, 100.0 * wins as f64 / games);
+        }
+
+        out
+    }
+}
+
+fn play_pairing(config: &Config, a: usize, b: usize, seed: &mut u64, results: &mut Vec<GameResult>) {
+    for g in 0..config.games {
+        let seats = if g % 2 == 0 { [a, b] } else { [b, a] };
+        *seed += 1;
+
+        let first = player::by_name(&config.players[seats[0]], *seed * 2, config.timeout);
+        let second = player::by_name(&config.players[seats[1]], *seed * 2 + 1, config.timeout);
+
+        // a player that can't start forfeits
+        let (winner, moves) = match (first, second) {
+            (Ok(first), Ok(second)) => play_game(config, &mut [first, second], *seed),
+            (Err(msg), _) => {
+                println!("err = {}", msg);
+                (Some(1), 0)
+            },
+            (_, Err(msg)) => {
+                println!("err = {}", msg);
+                (Some(0), 0)
+            },
+        };
+
+        results.push(GameResult { seats, winner, moves });
+    }
+}
+
+// pairs players with the same score, without rematches where that is possible
+fn swiss_pairs(standings: &Standings, played: &HashSet<(usize, usize)>) -> (Vec<(usize, usize)>, Option<usize>) {
+    let mut order: Vec<usize> = (0..standings.names.len()).collect();
+    order.sort_by_key(|&p| ::std::cmp::Reverse(standings.points(p)));
+
+    // the lowest ranked player without a bye sits out
+    let bye = if order.len() % 2 == 1 {
+        let i = order.iter().rposition(|p| !standings.byes.contains(p)).unwrap_or(order.len() - 1);
+        Some(order.remove(i))
+    } else {
+        None
+    };
+
+    let mut pairs = Vec::new();
+    while !order.is_empty() {
+        let a = order.remove(0);
+        let i = order.iter().position(|&b| !played.contains(&(a.min(b), a.max(b)))).unwrap_or(0);
+        pairs.push((a, order.remove(i)));
+    }
+
+    (pairs, bye)
+}
+
+pub fn run(config: &Config) -> Standings {
+    let n = config.players.len();
+    let mut standings = Standings { names: config.players.clone(), results: Vec::new(), byes: Vec::new() };
+    let mut seed = config.seed;
+
+    match config.format {
+        Format::RoundRobin => for a in 0..n {
+            for b in a + 1..n {
+                play_pairing(config, a, b, &mut seed, &mut standings.results);
+            }
+        },
+        Format::Swiss(rounds) => {
+            let mut played = HashSet::new();
+
+            for _ in 0..rounds {
+                let (pairs, bye) = swiss_pairs(&standings, &played);
+                standings.byes.extend(bye);
+
+                for (a, b) in pairs {
+                    played.insert((a.min(b), a.max(b)));
+                    play_pairing(config, a, b, &mut seed, &mut standings.results);
+                }
+            }
+        },
+    }
+
+    standings
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Config::from_args(&args).unwrap()
+    }
+
+    #[test]
+    fn round_robin_plays_every_pairing() {
+        let config = config(&["random", "greedy", "random", "--games", "2", "--max-moves", "60"]);
+        let standings = run(&config);
+
+        assert_eq!(standings.results.len(), 3 * 2);
+        // every pairing swaps seats between its games
+        assert_eq!(standings.results[0].seats, [0, 1]);
+        assert_eq!(standings.results[1].seats, [1, 0]);
+        assert!(standings.results.iter().all(|r| r.moves <= 60));
+
+        let games: u32 = (0..3).map(|p| { let (w, d, l) = standings.record(p); w + d + l }).sum();
+        assert_eq!(games, 2 * 6);
+    }
+
+    #[test]
+    fn same_seed_same_games() {
+        let plain = config(&["greedy", "random", "--games", "4", "--max-moves", "200", "--seed", "7"]);
+        assert_eq!(run(&plain).results, run(&plain).results);
+
+        // bots that choose on a fogged view roll the same upgrades as the real game
+        let fogged = config(&["random", "greedy", "--fog", "reach", "--games", "6", "--seed", "5"]);
+        assert_eq!(run(&fogged).results, run(&fogged).results);
+    }
+
+    #[test]
+    fn swiss_gives_out_byes() {
+        let config = config(&["random", "random", "greedy", "--swiss", "2", "--games", "1", "--max-moves", "40"]);
+        let standings = run(&config);
+
+        assert_eq!(standings.results.len(), 2);
+        assert_eq!(standings.byes.len(), 2);
+        assert_ne!(standings.byes[0], standings.byes[1]);
+        assert!(standings.report().contains("2 games"));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let args = |a: &[&str]| Config::from_args(&a.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+
+        assert!(args(&["random"]).is_err());
+        assert!(args(&["random", "nobody"]).is_err());
+        assert!(args(&["--games"]).is_err());
+        assert!(args(&["random", "engine:./bot --fast"]).is_ok());
+        assert!(args(&["random", "greedy", "--rules", "strict"]).is_ok());
+        assert!(args(&["random", "greedy", "--rules", "chess"]).is_err());
+        assert!(args(&["random", "greedy", "--fog", "reach"]).is_ok());
+        assert!(args(&["random", "greedy", "--fog", "far"]).is_err());
+    }
+}